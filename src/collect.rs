@@ -0,0 +1,170 @@
+use std::{fmt,
+          io::{self, Write},
+          path::{Path, PathBuf}};
+
+use prometheus::{Encoder, TextEncoder};
+
+use crate::metrics_collector::{Metrics, OpMetricsCollector};
+
+/// Options of the one-shot collection.
+#[derive(clap::Args, Debug)]
+pub(crate) struct CollectArgs {
+    /// File to write metrics to, e.g. `/var/lib/node_exporter/textfile/onepassword.prom`. Printed to stdout if not set.
+    #[arg(short, long, env = "OP_EXPORTER_OUTPUT")]
+    output: Option<PathBuf>,
+
+    /// Output format. JSON is the inventory snapshot also served at `/api/v1/snapshot`.
+    #[arg(long, env = "OP_EXPORTER_FORMAT", value_enum, default_value_t = OutputFormat::Prometheus)]
+    format: OutputFormat,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub(crate) enum OutputFormat {
+    Prometheus,
+    Json,
+}
+
+/// Error of the `collect` and `push` subcommands, each kind exiting with its own status code.
+#[derive(Debug)]
+pub(crate) enum OneShotError {
+    /// Some collectors failed. Metrics collected are still written or pushed.
+    Collect(Vec<Metrics>),
+    /// Failed to write metrics.
+    Write(PathBuf, io::Error),
+    /// Failed to push metrics to the Pushgateway.
+    Push(String),
+}
+
+impl OneShotError {
+    /// Exit status code of the process for this error.
+    pub(crate) fn exit_code(&self) -> i32 {
+        match self {
+            OneShotError::Collect(_) => 3,
+            OneShotError::Write(..) | OneShotError::Push(_) => 4,
+        }
+    }
+}
+
+impl fmt::Display for OneShotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OneShotError::Collect(metrics) => {
+                let metrics: Vec<String> = metrics.iter().map(ToString::to_string).collect();
+                write!(f, "failed to collect {} metrics", metrics.join(", "))
+            }
+            OneShotError::Write(path, err) => {
+                write!(f, "failed to write metrics to {}: {err}", path.display())
+            }
+            OneShotError::Push(err) => write!(f, "failed to push metrics: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for OneShotError {}
+
+/// Collect given metrics once, failing with the collectors failed.
+pub(crate) async fn collect_once(
+    metrics_collector: &OpMetricsCollector,
+    metrics: Vec<Metrics>,
+) -> Result<(), OneShotError> {
+    let failed: Vec<Metrics> = metrics_collector
+        .collect(metrics)
+        .await
+        .into_iter()
+        .filter_map(|(metric, result)| result.is_err().then_some(metric))
+        .collect();
+
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(OneShotError::Collect(failed))
+    }
+}
+
+/// Collect given metrics once and write them in the requested format.
+pub(crate) async fn run(
+    metrics_collector: OpMetricsCollector,
+    metrics: Vec<Metrics>,
+    args: &CollectArgs,
+) -> Result<(), OneShotError> {
+    let collected = collect_once(&metrics_collector, metrics).await;
+
+    let mut buffer = vec![];
+    match args.format {
+        OutputFormat::Prometheus => TextEncoder::new()
+            .encode(&metrics_collector.registry().gather(), &mut buffer)
+            .unwrap(),
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut buffer, &metrics_collector.snapshot()).unwrap();
+            buffer.push(b'\n');
+        }
+    }
+    match &args.output {
+        Some(path) => {
+            write_atomically(path, &buffer)
+                .map_err(|err| OneShotError::Write(path.clone(), err))?;
+            log::info!("Wrote metrics to {}", path.display());
+        }
+        None => io::stdout()
+            .write_all(&buffer)
+            .map_err(|err| OneShotError::Write(PathBuf::from("stdout"), err))?,
+    }
+
+    collected
+}
+
+/// Write to a temporary file next to the path then rename it, so readers never see a partial file.
+fn write_atomically(path: &Path, content: &[u8]) -> io::Result<()> {
+    // node_exporter textfile collector ignores files not ending with `.prom`
+    let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(format!(".{}.tmp", std::process::id()));
+    let temp_path = path.with_file_name(temp_name);
+
+    let result =
+        std::fs::write(&temp_path, content).and_then(|_| std::fs::rename(&temp_path, path));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exit_code() {
+        assert_eq!(OneShotError::Collect(vec![Metrics::Item]).exit_code(), 3);
+        assert_eq!(OneShotError::Push("refused".to_string()).exit_code(), 4);
+        assert_eq!(
+            OneShotError::Write(PathBuf::from("out.prom"), io::ErrorKind::NotFound.into())
+                .exit_code(),
+            4
+        );
+        assert_eq!(
+            OneShotError::Collect(vec![Metrics::Item, Metrics::ServiceAccount]).to_string(),
+            "failed to collect item, service-account metrics"
+        );
+    }
+
+    #[test]
+    fn test_write_atomically() -> anyhow::Result<()> {
+        // Arrange
+        let dir = std::env::temp_dir().join(format!("op-exporter-textfile-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join("onepassword.prom");
+        std::fs::write(&path, "old")?;
+
+        // Act
+        write_atomically(&path, b"new")?;
+
+        // Assert
+        assert_eq!(std::fs::read_to_string(&path)?, "new");
+        assert_eq!(std::fs::read_dir(&dir)?.count(), 1);
+        assert!(write_atomically(&dir.join("missing").join("onepassword.prom"), b"new").is_err());
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
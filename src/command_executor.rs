@@ -1,49 +1,210 @@
-use std::process::Command;
+use std::{fmt, time::Duration};
 
+use async_trait::async_trait;
+use lazy_static::lazy_static;
 #[cfg(test)]
 use mockall::{predicate::*, *};
+use regex::Regex;
+use tokio::{process::Command, sync::Semaphore};
+
+lazy_static! {
+    static ref SERVICE_ACCOUNT_TOKEN: Regex = Regex::new(r"ops_[A-Za-z0-9_\-.=]+").unwrap();
+}
 
 #[cfg_attr(test, automock)]
-pub(crate) trait CommandExecutor {
-    fn exec(&self, args: Vec<&'static str>) -> Result<String, std::io::Error>;
+#[async_trait]
+pub(crate) trait CommandExecutor: Send + Sync {
+    async fn exec(&self, args: Vec<&'static str>) -> Result<String, OpError>;
+
+    /// Execute a quick command, such as a readiness check, without waiting behind the running collections.
+    async fn exec_unqueued(&self, args: Vec<&'static str>) -> Result<String, OpError>;
+}
+
+/// Classification of the 1Password CLI failure, based on its error message.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) enum OpErrorKind {
+    Authentication,
+    RateLimited,
+    PermissionDenied,
+    NotFound,
+    Unknown,
+}
+
+impl OpErrorKind {
+    fn classify(stderr: &str) -> Self {
+        let stderr = stderr.to_lowercase();
+        let contains_any = |patterns: &[&str]| patterns.iter().any(|p| stderr.contains(p));
+
+        if contains_any(&["too many requests", "rate limit", "(429)"]) {
+            OpErrorKind::RateLimited
+        } else if contains_any(&[
+            "not currently signed in",
+            "authentication",
+            "unauthorized",
+            "invalid token",
+            "invalid bearer token",
+            "service account token",
+            "(401)",
+        ]) {
+            OpErrorKind::Authentication
+        } else if contains_any(&["permission", "forbidden", "(403)"]) {
+            OpErrorKind::PermissionDenied
+        } else if contains_any(&["not found", "isn't a", "isn't an", "(404)"]) {
+            OpErrorKind::NotFound
+        } else {
+            OpErrorKind::Unknown
+        }
+    }
+
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            OpErrorKind::Authentication => "authentication",
+            OpErrorKind::RateLimited => "rate_limited",
+            OpErrorKind::PermissionDenied => "permission_denied",
+            OpErrorKind::NotFound => "not_found",
+            OpErrorKind::Unknown => "unknown",
+        }
+    }
+}
+
+/// Error occurred while executing the 1Password CLI.
+#[derive(Debug)]
+pub(crate) enum OpError {
+    /// Failed to run the process, e.g. binary not found.
+    Io(std::io::Error),
+    /// Process did not exit within the timeout, and has been killed.
+    Timeout(Duration),
+    /// Process exited with non-zero status.
+    Failed {
+        kind: OpErrorKind,
+        exit_code: Option<i32>,
+        /// Error output of the process, with secrets redacted.
+        stderr: String,
+    },
+}
+
+impl OpError {
+    /// Short name of the error kind, used as metric label.
+    pub(crate) fn kind(&self) -> &'static str {
+        match self {
+            OpError::Io(_) => "io",
+            OpError::Timeout(_) => "timeout",
+            OpError::Failed { kind, .. } => kind.as_str(),
+        }
+    }
+}
+
+impl fmt::Display for OpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OpError::Io(err) => write!(f, "{err}"),
+            OpError::Timeout(timeout) => write!(
+                f,
+                "timed out after {}",
+                humantime::format_duration(*timeout)
+            ),
+            OpError::Failed {
+                kind,
+                exit_code,
+                stderr,
+            } => {
+                let exit_code = exit_code.map_or("none".to_string(), |c| c.to_string());
+                write!(
+                    f,
+                    "exited with status {exit_code} ({}): {stderr}",
+                    kind.as_str()
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for OpError {}
+
+impl From<std::io::Error> for OpError {
+    fn from(err: std::io::Error) -> Self {
+        OpError::Io(err)
+    }
+}
+
+/// Redact secrets possibly included in the CLI output.
+fn redact(s: &str) -> String {
+    SERVICE_ACCOUNT_TOKEN
+        .replace_all(s, "ops_<redacted>")
+        .to_string()
 }
 
 pub(crate) struct OpCommandExecutor {
     op_path: String,
     service_account_token: Option<String>,
+    timeout: Duration,
+    /// Limits the number of `op` processes running at the same time.
+    semaphore: Semaphore,
 }
 
 impl OpCommandExecutor {
-    pub(crate) fn new(op_path: String, service_account_token: Option<String>) -> Self {
+    /// Create an executor running at most `max_concurrency` commands at once, which must be at least 1.
+    pub(crate) fn new(
+        op_path: String,
+        service_account_token: Option<String>,
+        timeout: Duration,
+        max_concurrency: usize,
+    ) -> Self {
         OpCommandExecutor {
             op_path,
             service_account_token,
+            timeout,
+            semaphore: Semaphore::new(max_concurrency),
         }
     }
-}
 
-impl CommandExecutor for OpCommandExecutor {
-    fn exec(&self, args: Vec<&'static str>) -> Result<String, std::io::Error> {
+    /// Run the command regardless of the concurrency limit.
+    async fn run(&self, args: Vec<&'static str>) -> Result<String, OpError> {
         let mut cmd: &mut Command = &mut Command::new(self.op_path.clone());
         if let Some(sa_token) = &self.service_account_token {
             cmd = cmd.env("OP_SERVICE_ACCOUNT_TOKEN", sa_token);
         }
-        let output = cmd.args(args).output()?;
-        let stdout: String = output.stdout.iter().map(|&x| x as char).collect();
+        // Child process is killed if the output future dropped on timeout
+        let output = cmd.args(args).kill_on_drop(true).output();
+        let output = tokio::time::timeout(self.timeout, output)
+            .await
+            .map_err(|_| OpError::Timeout(self.timeout))??;
+        if !output.status.success() {
+            let stderr = redact(String::from_utf8_lossy(&output.stderr).trim());
+            return Err(OpError::Failed {
+                kind: OpErrorKind::classify(&stderr),
+                exit_code: output.status.code(),
+                stderr,
+            });
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+#[async_trait]
+impl CommandExecutor for OpCommandExecutor {
+    async fn exec(&self, args: Vec<&'static str>) -> Result<String, OpError> {
+        let _permit = self.semaphore.acquire().await.unwrap();
+        self.run(args).await
+    }
 
-        Ok(stdout)
+    async fn exec_unqueued(&self, args: Vec<&'static str>) -> Result<String, OpError> {
+        self.run(args).await
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use rstest::*;
+
     use super::*;
+    use crate::test_helper::MOCK_OP;
 
-    #[test]
-    fn test_command_executor() {
+    #[tokio::test]
+    async fn test_command_executor() {
         // Arrange
-        let executor = OpCommandExecutor::new("op".to_string(), None);
-        let output = executor.exec(vec!["--version"]).unwrap();
+        let executor = OpCommandExecutor::new("op".to_string(), None, Duration::from_secs(10), 1);
+        let output = executor.exec(vec!["--version"]).await.unwrap();
 
         // Act
         let version_info = output.trim().split(".").collect::<Vec<&str>>();
@@ -54,4 +215,175 @@ fn test_command_executor() {
         assert!(version_info[1].parse::<i32>().is_ok());
         assert!(version_info[2].parse::<i32>().is_ok());
     }
+
+    #[tokio::test]
+    async fn test_command_executor_failure() {
+        // Arrange
+        let executor =
+            OpCommandExecutor::new(MOCK_OP.to_string(), None, Duration::from_secs(10), 1);
+
+        // Act
+        let err = executor.exec(vec!["unknown", "command"]).await.unwrap_err();
+
+        // Assert
+        match err {
+            OpError::Failed {
+                kind,
+                exit_code,
+                stderr,
+            } => {
+                assert_eq!(kind, OpErrorKind::Unknown);
+                assert_eq!(exit_code, Some(255));
+                assert!(stderr.contains("unknown command"));
+            }
+            _ => panic!("unexpected error: {err:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_command_executor_not_found() {
+        // Arrange
+        let executor = OpCommandExecutor::new(
+            "/nonexistent/op".to_string(),
+            None,
+            Duration::from_secs(10),
+            1,
+        );
+
+        // Act
+        let err = executor.exec(vec!["--version"]).await.unwrap_err();
+
+        // Assert
+        assert!(matches!(err, OpError::Io(_)));
+        assert_eq!(err.kind(), "io");
+    }
+
+    #[tokio::test]
+    async fn test_command_executor_timeout() {
+        // Arrange
+        let executor =
+            OpCommandExecutor::new("sleep".to_string(), None, Duration::from_millis(100), 1);
+
+        // Act
+        let start = std::time::Instant::now();
+        let err = executor.exec(vec!["10"]).await.unwrap_err();
+
+        // Assert
+        assert!(matches!(err, OpError::Timeout(_)));
+        assert_eq!(err.kind(), "timeout");
+        assert!(start.elapsed() < Duration::from_secs(10));
+    }
+
+    #[tokio::test]
+    async fn test_command_executor_unqueued() {
+        // Arrange
+        let executor = OpCommandExecutor::new("sh".to_string(), None, Duration::from_secs(10), 1);
+        let held = executor.exec(vec!["-c", "sleep 10"]);
+        tokio::pin!(held);
+
+        // Act
+        // Biased to start the held command first, taking the only permit
+        let output = tokio::select! {
+            biased;
+            _ = &mut held => panic!("held command finished first"),
+            output = executor.exec_unqueued(vec!["-c", "echo ok"]) => output,
+        };
+
+        // Assert
+        assert_eq!(output.unwrap(), "ok\n");
+    }
+
+    /// Prints the number of its invocations running at the same time, keeping marker files next to itself.
+    ///
+    /// Each invocation holds until `$1` of them are running, or all that remain of `$2` in total, so the peak
+    /// does not depend on how long the processes happen to overlap.
+    const CONCURRENCY_SCRIPT: &str = r#"dir=$(dirname "$0") limit=$1 total=$2
+touch "$dir/$$.start"
+while :; do
+    markers=$(ls "$dir")
+    running=$(( $(echo "$markers" | grep -c start) - $(echo "$markers" | grep -c end) ))
+    remaining=$(( total - $(echo "$markers" | grep -c end) ))
+    [ "$running" -ge "$limit" ] || [ "$running" -ge "$remaining" ] && break
+    sleep 0.01
+done
+echo "$running"
+touch "$dir/$$.end"
+"#;
+
+    #[rstest]
+    #[case(1)]
+    #[case(2)]
+    #[tokio::test]
+    async fn test_command_executor_concurrency(#[case] max_concurrency: usize) {
+        // Arrange
+        let dir = std::env::temp_dir().join(format!(
+            "op-exporter-concurrency-{}-{max_concurrency}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let script = dir.join("concurrency.sh");
+        std::fs::write(&script, CONCURRENCY_SCRIPT).unwrap();
+        // Arguments are `'static` as the collectors pass literals
+        let script: &'static str = Box::leak(script.to_str().unwrap().into());
+        let limit: &'static str = Box::leak(max_concurrency.to_string().into());
+        let executor = OpCommandExecutor::new(
+            "sh".to_string(),
+            None,
+            Duration::from_secs(10),
+            max_concurrency,
+        );
+
+        // Act
+        let outputs =
+            futures::future::join_all((0..3).map(|_| executor.exec(vec![script, limit, "3"])))
+                .await;
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // Assert
+        let running = outputs
+            .into_iter()
+            .map(|output| output.unwrap().trim().parse::<usize>().unwrap())
+            .max();
+        assert_eq!(running, Some(max_concurrency));
+    }
+
+    #[test]
+    fn test_op_error_kind_classify() {
+        assert_eq!(
+            OpErrorKind::classify("[ERROR] 2024/08/01 12:00:00 You are not currently signed in."),
+            OpErrorKind::Authentication
+        );
+        assert_eq!(
+            OpErrorKind::classify("[ERROR] 2024/08/01 12:00:00 (401) Unauthorized: You aren't authorized to perform this action."),
+            OpErrorKind::Authentication
+        );
+        assert_eq!(
+            OpErrorKind::classify(
+                "[ERROR] 2024/08/01 12:00:00 Too many requests. Your client has been rate-limited."
+            ),
+            OpErrorKind::RateLimited
+        );
+        assert_eq!(
+            OpErrorKind::classify("[ERROR] 2024/08/01 12:00:00 (403) Forbidden: You don't have the necessary permission."),
+            OpErrorKind::PermissionDenied
+        );
+        assert_eq!(
+            OpErrorKind::classify(
+                "[ERROR] 2024/08/01 12:00:00 \"Private\" isn't a vault in this account."
+            ),
+            OpErrorKind::NotFound
+        );
+        assert_eq!(
+            OpErrorKind::classify("[ERROR] 2024/08/01 12:00:00 something went wrong"),
+            OpErrorKind::Unknown
+        );
+    }
+
+    #[test]
+    fn test_redact() {
+        assert_eq!(
+            redact("invalid token ops_eyJzaWduSW5BZGRyZXNzIjoi= given"),
+            "invalid token ops_<redacted> given"
+        );
+    }
 }
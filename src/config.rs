@@ -0,0 +1,466 @@
+use std::{collections::{BTreeMap, HashMap},
+          convert::Infallible,
+          fmt,
+          path::{Path, PathBuf},
+          str::FromStr,
+          time::Duration};
+
+use serde::{Deserialize, Deserializer};
+use simplelog::LevelFilter;
+
+use crate::metrics_collector::{Metrics, StaleThreshold};
+
+/// Configuration file of the exporter, in TOML or YAML.
+///
+/// Top-level keys mirror the command line options, which take precedence over values set here.
+#[derive(Deserialize, Debug, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Config {
+    pub(crate) log_level: Option<LevelFilter>,
+    pub(crate) host: Option<String>,
+    pub(crate) port: Option<u16>,
+    pub(crate) metrics: Option<Vec<Metrics>>,
+    pub(crate) op_path: Option<String>,
+    pub(crate) service_account_token: Option<Secret>,
+    pub(crate) service_account_token_file: Option<PathBuf>,
+    #[serde(default, deserialize_with = "deserialize_duration")]
+    pub(crate) op_timeout: Option<Duration>,
+    pub(crate) op_concurrency: Option<usize>,
+    #[serde(default, deserialize_with = "deserialize_duration")]
+    pub(crate) collect_interval: Option<Duration>,
+    pub(crate) readiness_stale_intervals: Option<u32>,
+    #[serde(default, deserialize_with = "deserialize_duration")]
+    pub(crate) shutdown_timeout: Option<Duration>,
+    pub(crate) web_config_file: Option<PathBuf>,
+    #[serde(default, deserialize_with = "deserialize_thresholds")]
+    pub(crate) stale_thresholds: Option<Vec<StaleThreshold>>,
+    /// Stale thresholds replacing `stale_thresholds` for items of a category.
+    #[serde(default, deserialize_with = "deserialize_category_thresholds")]
+    pub(crate) stale_category_thresholds: HashMap<String, Vec<StaleThreshold>>,
+    pub(crate) editor_ids_only: Option<bool>,
+
+    /// Settings of each collector.
+    #[serde(default)]
+    pub(crate) collectors: HashMap<Metrics, CollectorConfig>,
+
+    /// Named targets served at `/probe?target=<name>`.
+    #[serde(default)]
+    pub(crate) targets: BTreeMap<String, TargetConfig>,
+}
+
+/// Settings of a collector.
+#[derive(Deserialize, Debug, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct CollectorConfig {
+    /// Collection interval, overriding the global one.
+    #[serde(default, deserialize_with = "deserialize_duration")]
+    pub(crate) interval: Option<Duration>,
+}
+
+/// A 1Password account to collect metrics from. Unset fields fall back to the global options.
+#[derive(Deserialize, Debug, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct TargetConfig {
+    pub(crate) op_path: Option<String>,
+    pub(crate) service_account_token: Option<Secret>,
+    pub(crate) service_account_token_file: Option<PathBuf>,
+    pub(crate) metrics: Option<Vec<Metrics>>,
+}
+
+/// Value which must not appear in logs, such as the service account token. Its `Debug` output is redacted.
+#[derive(Clone, Deserialize, PartialEq)]
+#[serde(transparent)]
+pub(crate) struct Secret(String);
+
+impl Secret {
+    /// Read secret from a file, such as Docker or Kubernetes secret mount, ignoring surrounding whitespaces.
+    pub(crate) fn read(path: &Path) -> std::io::Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        Ok(Secret(content.trim().to_string()))
+    }
+
+    pub(crate) fn into_inner(self) -> String {
+        self.0
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<redacted>")
+    }
+}
+
+impl FromStr for Secret {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Secret(s.to_string()))
+    }
+}
+
+/// Deserialize human-readable duration such as `30s` or `6h`.
+fn deserialize_duration<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|s| humantime::parse_duration(&s).map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+fn parse_thresholds(values: &[String]) -> Result<Vec<StaleThreshold>, humantime::DurationError> {
+    values.iter().map(|s| s.parse()).collect()
+}
+
+/// Deserialize list of stale thresholds, human-readable durations kept as written.
+fn deserialize_thresholds<'de, D>(deserializer: D) -> Result<Option<Vec<StaleThreshold>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<Vec<String>>::deserialize(deserializer)?
+        .map(|values| parse_thresholds(&values).map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+/// Deserialize mapping of names to lists of stale thresholds.
+fn deserialize_category_thresholds<'de, D>(
+    deserializer: D,
+) -> Result<HashMap<String, Vec<StaleThreshold>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    HashMap::<String, Vec<String>>::deserialize(deserializer)?
+        .into_iter()
+        .map(|(name, values)| parse_thresholds(&values).map(|thresholds| (name, thresholds)))
+        .collect::<Result<_, _>>()
+        .map_err(serde::de::Error::custom)
+}
+
+/// Error occurred while loading the configuration file.
+#[derive(Debug)]
+pub(crate) enum ConfigError {
+    /// Failed to read the file.
+    Io(std::io::Error),
+    /// File is not a valid TOML configuration.
+    Toml(toml::de::Error),
+    /// File is not a valid YAML configuration.
+    Yaml(serde_yaml_ng::Error),
+    /// A value is well-formed but not acceptable.
+    Invalid { key: String, message: String },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(err) => write!(f, "failed to read config file: {err}"),
+            ConfigError::Toml(err) => write!(f, "invalid config file: {err}"),
+            ConfigError::Yaml(err) => write!(f, "invalid config file: {err}"),
+            ConfigError::Invalid { key, message } => {
+                write!(f, "invalid config file: `{key}` {message}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<std::io::Error> for ConfigError {
+    fn from(err: std::io::Error) -> Self {
+        ConfigError::Io(err)
+    }
+}
+
+impl From<toml::de::Error> for ConfigError {
+    fn from(err: toml::de::Error) -> Self {
+        ConfigError::Toml(err)
+    }
+}
+
+impl From<serde_yaml_ng::Error> for ConfigError {
+    fn from(err: serde_yaml_ng::Error) -> Self {
+        ConfigError::Yaml(err)
+    }
+}
+
+impl Config {
+    /// Load configuration from a file, parsed as YAML if its extension is `.yaml` or `.yml` and TOML otherwise.
+    pub(crate) fn load(path: &Path) -> Result<Self, ConfigError> {
+        let content = std::fs::read_to_string(path)?;
+        let config = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("yaml" | "yml") => Self::parse_yaml(&content)?,
+            _ => Self::parse_toml(&content)?,
+        };
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    fn parse_toml(content: &str) -> Result<Self, ConfigError> {
+        Ok(toml::from_str(content)?)
+    }
+
+    fn parse_yaml(content: &str) -> Result<Self, ConfigError> {
+        // Empty YAML document is null, not an empty mapping
+        if content.trim().is_empty() {
+            return Ok(Config::default());
+        }
+        Ok(serde_yaml_ng::from_str(content)?)
+    }
+
+    /// Check values that can be parsed but would not work.
+    fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |key: String, message: &str| {
+            Err(ConfigError::Invalid {
+                key,
+                message: message.to_string(),
+            })
+        };
+
+        if self.service_account_token.is_some() && self.service_account_token_file.is_some() {
+            return invalid(
+                "service_account_token_file".to_string(),
+                "cannot be set with `service_account_token`",
+            );
+        }
+        if self.metrics.as_ref().is_some_and(Vec::is_empty) {
+            return invalid("metrics".to_string(), "must not be empty");
+        }
+        if self.op_concurrency == Some(0) {
+            return invalid("op_concurrency".to_string(), "must be at least 1");
+        }
+        if self.op_timeout == Some(Duration::ZERO) {
+            return invalid("op_timeout".to_string(), "must not be zero");
+        }
+        if self.collect_interval == Some(Duration::ZERO) {
+            return invalid("collect_interval".to_string(), "must not be zero");
+        }
+        if self.readiness_stale_intervals == Some(0) {
+            return invalid(
+                "readiness_stale_intervals".to_string(),
+                "must be at least 1",
+            );
+        }
+        for (metric, collector) in &self.collectors {
+            if collector.interval == Some(Duration::ZERO) {
+                return invalid(format!("collectors.{metric}.interval"), "must not be zero");
+            }
+        }
+        for (name, target) in &self.targets {
+            if target.service_account_token.is_some() && target.service_account_token_file.is_some()
+            {
+                return invalid(
+                    format!("targets.{name}.service_account_token_file"),
+                    "cannot be set with `service_account_token`",
+                );
+            }
+            if target.metrics.as_ref().is_some_and(Vec::is_empty) {
+                return invalid(format!("targets.{name}.metrics"), "must not be empty");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_toml() {
+        let config = Config::parse_toml(
+            r#"
+            log_level = "debug"
+            port = 9100
+            metrics = ["service-account", "item"]
+            op_timeout = "1m"
+            stale_thresholds = ["90d", "1y"]
+            editor_ids_only = true
+
+            [stale_category_thresholds]
+            API_CREDENTIAL = ["30d"]
+
+            [collectors.item]
+            interval = "6h"
+
+            [targets.family]
+            service_account_token = "ops_family"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config,
+            Config {
+                log_level: Some(LevelFilter::Debug),
+                port: Some(9100),
+                metrics: Some(vec![Metrics::ServiceAccount, Metrics::Item]),
+                op_timeout: Some(Duration::from_secs(60)),
+                stale_thresholds: Some(vec!["90d".parse().unwrap(), "1y".parse().unwrap()]),
+                stale_category_thresholds: HashMap::from([(
+                    "API_CREDENTIAL".to_string(),
+                    vec!["30d".parse().unwrap()]
+                )]),
+                editor_ids_only: Some(true),
+                collectors: HashMap::from([(
+                    Metrics::Item,
+                    CollectorConfig {
+                        interval: Some(Duration::from_secs(6 * 60 * 60)),
+                    }
+                )]),
+                targets: BTreeMap::from([(
+                    "family".to_string(),
+                    TargetConfig {
+                        service_account_token: Some(Secret("ops_family".to_string())),
+                        ..Default::default()
+                    }
+                )]),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_yaml() {
+        let config = Config::parse_yaml(
+            r#"
+            host: 0.0.0.0
+            collect_interval: 5m
+            collectors:
+              service-account:
+                interval: 30s
+            targets:
+              business:
+                op_path: /usr/local/bin/op
+                metrics: [group, vault]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config,
+            Config {
+                host: Some("0.0.0.0".to_string()),
+                collect_interval: Some(Duration::from_secs(5 * 60)),
+                collectors: HashMap::from([(
+                    Metrics::ServiceAccount,
+                    CollectorConfig {
+                        interval: Some(Duration::from_secs(30)),
+                    }
+                )]),
+                targets: BTreeMap::from([(
+                    "business".to_string(),
+                    TargetConfig {
+                        op_path: Some("/usr/local/bin/op".to_string()),
+                        metrics: Some(vec![Metrics::Group, Metrics::Vault]),
+                        ..Default::default()
+                    }
+                )]),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_empty() {
+        assert_eq!(Config::parse_toml("").unwrap(), Config::default());
+        assert_eq!(Config::parse_yaml("").unwrap(), Config::default());
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        let err = Config::parse_toml(
+            r#"
+            [targets.family]
+            metrics = ["unknown"]
+            "#,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("unknown variant `unknown`"));
+
+        let err = Config::parse_toml(
+            r#"
+            [targets.family]
+            token = "ops_family"
+            "#,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("unknown field `token`"));
+
+        let err = Config::parse_yaml(
+            r#"
+            collectors:
+              item:
+                interval: forever
+            "#,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("collectors.item"));
+        assert!(err.to_string().contains("line 4 column 17"));
+    }
+
+    #[test]
+    fn test_validate() {
+        let config = Config::parse_toml(
+            r#"
+            [collectors.item]
+            interval = "0s"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.validate().unwrap_err().to_string(),
+            "invalid config file: `collectors.item.interval` must not be zero"
+        );
+
+        let config = Config::parse_toml("metrics = []").unwrap();
+        assert_eq!(
+            config.validate().unwrap_err().to_string(),
+            "invalid config file: `metrics` must not be empty"
+        );
+
+        let config = Config::parse_toml("op_concurrency = 0").unwrap();
+        assert_eq!(
+            config.validate().unwrap_err().to_string(),
+            "invalid config file: `op_concurrency` must be at least 1"
+        );
+
+        let config = Config::parse_toml(
+            r#"
+            [targets.family]
+            service_account_token = "ops_family"
+            service_account_token_file = "/run/secrets/op_token"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.validate().unwrap_err().to_string(),
+            "invalid config file: `targets.family.service_account_token_file` cannot be set with `service_account_token`"
+        );
+
+        assert!(Config::default().validate().is_ok());
+    }
+
+    #[test]
+    fn test_secret() {
+        let config = Config::parse_toml(r#"service_account_token = "ops_secret""#).unwrap();
+
+        assert!(!format!("{config:?}").contains("ops_secret"));
+        assert_eq!(
+            config.service_account_token.unwrap().into_inner(),
+            "ops_secret"
+        );
+    }
+
+    #[test]
+    fn test_secret_read() -> std::io::Result<()> {
+        let path = std::env::temp_dir().join(format!("op-exporter-token-{}", std::process::id()));
+        std::fs::write(&path, "ops_secret\n")?;
+
+        let secret = Secret::read(&path);
+        std::fs::remove_file(&path)?;
+
+        assert_eq!(secret?, Secret("ops_secret".to_string()));
+        Ok(())
+    }
+}
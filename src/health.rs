@@ -0,0 +1,283 @@
+use std::{collections::{BTreeMap, HashMap},
+          sync::Arc,
+          time::{Duration, SystemTime}};
+
+use serde::Serialize;
+
+use crate::scheduler::Scheduler;
+
+/// Result of a single readiness check.
+#[derive(Serialize, Debug, PartialEq)]
+pub(crate) struct Check {
+    pub(crate) status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) error: Option<String>,
+}
+
+impl Check {
+    fn ok() -> Self {
+        Check {
+            status: "ok",
+            error: None,
+        }
+    }
+
+    fn fail(error: impl ToString) -> Self {
+        Check {
+            status: "fail",
+            error: Some(error.to_string()),
+        }
+    }
+
+    fn is_ok(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// Readiness report, serialized as the body of `/readyz`.
+#[derive(Serialize, Debug)]
+pub(crate) struct Readiness {
+    pub(crate) status: &'static str,
+    pub(crate) checks: BTreeMap<&'static str, Check>,
+    /// Readiness of each target, by name.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) targets: BTreeMap<String, Readiness>,
+}
+
+impl Readiness {
+    pub(crate) fn is_ready(&self) -> bool {
+        self.checks.values().all(Check::is_ok) && self.targets.values().all(Readiness::is_ready)
+    }
+
+    fn with_status(mut self) -> Self {
+        self.status = if self.is_ready() { "ok" } else { "fail" };
+        self
+    }
+}
+
+/// Check readiness of the default instance, if collected, and every target.
+pub(crate) async fn check_server_readiness(
+    scheduler: Option<&Scheduler>,
+    targets: &HashMap<String, Arc<Scheduler>>,
+    stale_intervals: u32,
+) -> Readiness {
+    let checks = targets.iter().map(|(name, scheduler)| async move {
+        (
+            name.clone(),
+            check_readiness(scheduler, stale_intervals).await,
+        )
+    });
+    let (readiness, targets) = tokio::join!(
+        async {
+            match scheduler {
+                Some(scheduler) => check_readiness(scheduler, stale_intervals).await,
+                None => Readiness {
+                    status: "ok",
+                    checks: BTreeMap::new(),
+                    targets: BTreeMap::new(),
+                },
+            }
+        },
+        futures::future::join_all(checks)
+    );
+
+    Readiness {
+        targets: targets.into_iter().collect(),
+        ..readiness
+    }
+    .with_status()
+}
+
+/// Check the 1Password CLI is usable and every collector succeeded within `stale_intervals` of its interval.
+///
+/// Results of the 1Password CLI checks are cached briefly, see [`op_checks`](crate::metrics_collector::OpMetricsCollector::op_checks).
+pub(crate) async fn check_readiness(scheduler: &Scheduler, stale_intervals: u32) -> Readiness {
+    let op_checks = scheduler.metrics_collector().op_checks().await;
+
+    let mut checks = BTreeMap::new();
+    checks.insert(
+        "op",
+        op_checks.version.map_or_else(Check::fail, |_| Check::ok()),
+    );
+    checks.insert(
+        "auth",
+        op_checks.auth.map_or_else(Check::fail, |_| Check::ok()),
+    );
+    checks.insert(
+        "collection",
+        check_collection(scheduler, stale_intervals, SystemTime::now()),
+    );
+
+    Readiness {
+        status: "ok",
+        checks,
+        targets: BTreeMap::new(),
+    }
+    .with_status()
+}
+
+fn check_collection(scheduler: &Scheduler, stale_intervals: u32, now: SystemTime) -> Check {
+    let statuses = scheduler.statuses();
+    let mut problems = vec![];
+    for (interval, metrics) in scheduler.schedule() {
+        let deadline = *interval * stale_intervals;
+        for metric in metrics {
+            let last_success = statuses.get(metric).and_then(|s| s.last_success);
+            match last_success {
+                None => problems.push(format!("{metric}: no successful collection yet")),
+                Some(t) if now.duration_since(t).unwrap_or(Duration::ZERO) > deadline => problems
+                    .push(format!(
+                        "{metric}: no successful collection in {}",
+                        humantime::format_duration(deadline)
+                    )),
+                Some(_) => {}
+            }
+        }
+    }
+
+    if problems.is_empty() {
+        Check::ok()
+    } else {
+        Check::fail(problems.join("; "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mockall::predicate::*;
+    use rstest::*;
+
+    use super::*;
+    use crate::{command_executor::MockCommandExecutor,
+                metrics_collector::{Metrics, OpMetricsCollector},
+                testing::metrics_collector};
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_not_ready_before_collection(metrics_collector: OpMetricsCollector) {
+        // Arrange
+        let scheduler = Scheduler::new(
+            metrics_collector,
+            vec![Metrics::Group],
+            Duration::from_secs(60),
+            &HashMap::new(),
+        );
+
+        // Act
+        let readiness = check_readiness(&scheduler, 3).await;
+
+        // Assert
+        assert!(!readiness.is_ready());
+        assert_eq!(readiness.status, "fail");
+        assert_eq!(readiness.checks["op"], Check::ok());
+        assert_eq!(readiness.checks["auth"], Check::ok());
+        assert_eq!(
+            readiness.checks["collection"],
+            Check::fail("group: no successful collection yet")
+        );
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_ready_after_collection(metrics_collector: OpMetricsCollector) {
+        // Arrange
+        let scheduler = Scheduler::new(
+            metrics_collector,
+            vec![Metrics::Group],
+            Duration::from_secs(60),
+            &HashMap::new(),
+        );
+        scheduler.collect(vec![Metrics::Group]).await;
+
+        // Act
+        let readiness = check_readiness(&scheduler, 3).await;
+
+        // Assert
+        assert!(readiness.is_ready());
+        assert_eq!(
+            serde_json::to_string(&readiness).unwrap(),
+            r#"{"status":"ok","checks":{"auth":{"status":"ok"},"collection":{"status":"ok"},"op":{"status":"ok"}}}"#
+        );
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_collection_stale(metrics_collector: OpMetricsCollector) {
+        // Arrange
+        let scheduler = Scheduler::new(
+            metrics_collector,
+            vec![Metrics::Group],
+            Duration::from_secs(60),
+            &HashMap::new(),
+        );
+        scheduler.collect(vec![Metrics::Group]).await;
+
+        // Act
+        let check = check_collection(&scheduler, 3, SystemTime::now() + Duration::from_secs(181));
+
+        // Assert
+        assert_eq!(check, Check::fail("group: no successful collection in 3m"));
+    }
+
+    #[tokio::test]
+    async fn test_op_checks_cached() {
+        // Arrange
+        let mut command_executor = MockCommandExecutor::new();
+        command_executor
+            .expect_exec_unqueued()
+            .with(eq(vec!["--version"]))
+            .times(1)
+            .returning(|_| Ok("2.30.0\n".to_string()));
+        command_executor
+            .expect_exec_unqueued()
+            .with(eq(vec!["whoami", "--format", "json"]))
+            .times(1)
+            .returning(|_| Ok("{}".to_string()));
+        let scheduler = Scheduler::new(
+            OpMetricsCollector::new(Box::new(command_executor)),
+            vec![],
+            Duration::from_secs(60),
+            &HashMap::new(),
+        );
+
+        // Act
+        let first = check_readiness(&scheduler, 3).await;
+        let second = check_readiness(&scheduler, 3).await;
+
+        // Assert
+        assert_eq!(first.checks["op"], Check::ok());
+        assert_eq!(second.checks["op"], Check::ok());
+        assert_eq!(second.checks["auth"], Check::ok());
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_targets_readiness(metrics_collector: OpMetricsCollector) {
+        // Arrange
+        let target = Scheduler::new(
+            metrics_collector,
+            vec![Metrics::Group],
+            Duration::from_secs(60),
+            &HashMap::new(),
+        );
+        let targets = HashMap::from([("family".to_string(), Arc::new(target))]);
+
+        // Act
+        let before = check_server_readiness(None, &targets, 3).await;
+        targets["family"].collect(vec![Metrics::Group]).await;
+        let after = check_server_readiness(None, &targets, 3).await;
+
+        // Assert
+        assert!(!before.is_ready());
+        assert_eq!(before.status, "fail");
+        assert_eq!(
+            before.targets["family"].checks["collection"],
+            Check::fail("group: no successful collection yet")
+        );
+        assert!(after.is_ready());
+        assert_eq!(
+            serde_json::to_string(&after).unwrap(),
+            r#"{"status":"ok","checks":{},"targets":{"family":{"status":"ok","checks":{"auth":{"status":"ok"},"collection":{"status":"ok"},"op":{"status":"ok"}}}}}"#
+        );
+    }
+}
@@ -0,0 +1,162 @@
+use std::{collections::HashMap, fmt::Write, sync::Arc};
+
+use crate::{metrics_collector::VERSION, scheduler::Scheduler};
+
+/// Escape text to embed in HTML.
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// Render HTML landing page describing the exporter instance.
+///
+/// `scheduler` is the default instance, `None` if only targets are collected.
+pub(crate) async fn render(
+    scheduler: Option<&Scheduler>,
+    op_path: &str,
+    targets: &HashMap<String, Arc<Scheduler>>,
+) -> String {
+    let mut op = escape(op_path);
+    let mut collectors = String::new();
+    let mut links = String::new();
+    match scheduler {
+        Some(scheduler) => {
+            match scheduler.metrics_collector().op_checks().await.version {
+                Ok(version) => write!(op, " ({})", escape(&version)).unwrap(),
+                Err(err) => write!(op, " (unknown ({}))", escape(&err)).unwrap(),
+            }
+            write!(
+                collectors,
+                r#"<table>
+<tr><th>Collector</th><th>Interval</th><th>Status</th><th>Last success</th><th>Error</th></tr>
+{}</table>
+"#,
+                collector_rows(scheduler)
+            )
+            .unwrap();
+            links.push_str(
+                r#"<li><a href="/metrics">Metrics</a></li>
+<li><a href="/api/v1/snapshot">Snapshot</a></li>
+"#,
+            );
+        }
+        None => collectors.push_str("<p>Only targets are collected.</p>\n"),
+    }
+
+    let mut target_names: Vec<&String> = targets.keys().collect();
+    target_names.sort();
+    let mut target_links = String::new();
+    for name in target_names {
+        let query: String = form_urlencoded::byte_serialize(name.as_bytes()).collect();
+        writeln!(
+            target_links,
+            r#"<li><a href="/probe?target={query}">Probe {name}</a></li>"#,
+            name = escape(name),
+        )
+        .unwrap();
+    }
+
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head><title>1Password Exporter</title></head>
+<body>
+<h1>1Password Exporter</h1>
+<dl>
+<dt>Version</dt><dd>{VERSION}</dd>
+<dt>1Password CLI</dt><dd>{op}</dd>
+</dl>
+<h2>Collectors</h2>
+{collectors}<ul>
+{links}<li><a href="/healthz">Health</a></li>
+<li><a href="/readyz">Readiness</a></li>
+{target_links}</ul>
+</body>
+</html>
+"#,
+    )
+}
+
+/// Render a table row per collector of given scheduler, with its last collection status.
+fn collector_rows(scheduler: &Scheduler) -> String {
+    let statuses = scheduler.statuses();
+
+    let mut rows = String::new();
+    for (interval, metrics) in scheduler.schedule() {
+        for metric in metrics {
+            let status = statuses.get(metric).cloned().unwrap_or_default();
+            let (result, error) = match (&status.last_attempt, &status.last_error) {
+                (None, _) => ("pending", String::new()),
+                (Some(_), None) => ("ok", String::new()),
+                (Some(_), Some(err)) => ("failed", escape(err)),
+            };
+            let last_success = status
+                .last_success
+                .map(|t| humantime::format_rfc3339_seconds(t).to_string())
+                .unwrap_or_else(|| "-".to_string());
+            writeln!(
+                rows,
+                "<tr><td>{metric}</td><td>{interval}</td><td>{result}</td><td>{last_success}</td><td>{error}</td></tr>",
+                interval = humantime::format_duration(*interval),
+            )
+            .unwrap();
+        }
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use rstest::*;
+
+    use super::*;
+    use crate::{metrics_collector::{Metrics, OpMetricsCollector},
+                testing::metrics_collector};
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_render(metrics_collector: OpMetricsCollector) {
+        // Arrange
+        let scheduler = Scheduler::new(
+            metrics_collector,
+            vec![Metrics::Group, Metrics::Vault],
+            Duration::from_secs(60),
+            &HashMap::new(),
+        );
+        scheduler.collect(vec![Metrics::Group]).await;
+
+        // Act
+        let page = render(Some(&scheduler), "/usr/bin/op", &HashMap::new()).await;
+
+        // Assert
+        assert!(page.contains(&format!("<dd>{VERSION}</dd>")));
+        assert!(page.contains("<dd>/usr/bin/op (2.30.0)</dd>"));
+        assert!(page.contains("<tr><td>group</td><td>1m</td><td>ok</td>"));
+        assert!(page.contains("<tr><td>vault</td><td>1m</td><td>pending</td><td>-</td>"));
+        assert!(page.contains(r#"<a href="/metrics">"#));
+    }
+
+    #[tokio::test]
+    async fn test_render_targets_only() {
+        // Act
+        let page = render(None, "/usr/bin/op", &HashMap::new()).await;
+
+        // Assert
+        assert!(page.contains("<dd>/usr/bin/op</dd>"));
+        assert!(page.contains("<p>Only targets are collected.</p>"));
+        assert!(!page.contains(r#"<a href="/metrics">"#));
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(
+            escape(r#"<a href="x">&'</a>"#),
+            "&lt;a href=&quot;x&quot;&gt;&amp;&#39;&lt;/a&gt;"
+        );
+    }
+}
@@ -1,11 +1,33 @@
-use clap::Parser;
+use std::{collections::HashMap,
+          path::{Path, PathBuf},
+          sync::Arc,
+          time::Duration};
+
+use clap::{builder::RangedU64ValueParser, error::ErrorKind, parser::ValueSource, ArgMatches,
+           CommandFactory, FromArgMatches, Parser, ValueEnum};
+use prometheus::Registry;
 use simplelog::*;
 
-use crate::metrics_collector::Metrics;
+use crate::{collect::CollectArgs,
+            command_executor::OpCommandExecutor,
+            config::Secret,
+            metrics_collector::{Metrics, OpMetricsCollector, StaleThreshold, StaleThresholds},
+            push::PushArgs,
+            scheduler::Scheduler,
+            server::ServerState,
+            web_config::WebConfig};
 
+mod collect;
 mod command_executor;
+mod config;
+mod health;
+mod landing_page;
 mod metrics_collector;
+mod openmetrics;
+mod push;
+mod scheduler;
 mod server;
+mod web_config;
 
 #[cfg(test)]
 mod testing;
@@ -18,56 +40,431 @@
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Args {
+    // Options the subcommands use too are global, so they can be given after the subcommand
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Path to configuration file in TOML or YAML. Options given in command line take precedence.
+    #[arg(long, env = "OP_EXPORTER_CONFIG", global = true)]
+    config: Option<PathBuf>,
+
     /// Log level.
-    #[arg(long, default_value_t = LevelFilter::Info)]
+    #[arg(long, env = "OP_EXPORTER_LOG_LEVEL", default_value_t = LevelFilter::Info, global = true)]
     log_level: LevelFilter,
 
     /// Host to bind the server to.
-    #[arg(long, default_value = "127.0.0.1")]
+    #[arg(long, env = "OP_EXPORTER_HOST", default_value = "127.0.0.1")]
     host: String,
 
     /// Port to bind the server to.
-    #[arg(short, long, default_value_t = 9999)]
+    #[arg(short, long, env = "OP_EXPORTER_PORT", default_value_t = 9999)]
     port: u16,
 
     /// Metrics to collect. Only metrics not consuming API rate enabled by default.
-    #[arg(short, long, num_args = 1.., value_delimiter = ',', default_values = ["account", "group", "user", "service-account", "build-info"])]
+    #[arg(short, long, env = "OP_EXPORTER_METRICS", num_args = 1.., value_delimiter = ',', default_values = ["account", "group", "user", "service-account", "build-info"], global = true)]
     metrics: Vec<Metrics>,
 
     /// Path to 1Password CLI binary.
-    #[arg(long, default_value = "op")]
+    #[arg(long, env = "OP_EXPORTER_OP_PATH", default_value = "op", global = true)]
     op_path: String,
 
-    /// Service account token to pass to the 1Password CLI.
-    #[arg(long)]
-    service_account_token: Option<String>,
+    /// Service account token to pass to the 1Password CLI. Prefer `--service-account-token-file` as this is visible in process list.
+    #[arg(
+        long,
+        env = "OP_EXPORTER_SERVICE_ACCOUNT_TOKEN",
+        hide_env_values = true,
+        global = true
+    )]
+    service_account_token: Option<Secret>,
+
+    /// Path to file containing service account token, such as Docker or Kubernetes secret.
+    #[arg(
+        long,
+        env = "OP_EXPORTER_SERVICE_ACCOUNT_TOKEN_FILE",
+        conflicts_with = "service_account_token",
+        global = true
+    )]
+    service_account_token_file: Option<PathBuf>,
+
+    /// Timeout for each 1Password CLI command, after which the command is killed.
+    #[arg(long, env = "OP_EXPORTER_OP_TIMEOUT", default_value = "30s", value_parser = parse_interval, global = true)]
+    op_timeout: Duration,
+
+    /// Maximum number of 1Password CLI commands running at the same time.
+    #[arg(long, env = "OP_EXPORTER_OP_CONCURRENCY", default_value_t = 4, value_parser = RangedU64ValueParser::<usize>::new().range(1..), global = true)]
+    op_concurrency: usize,
+
+    /// Interval between metrics collections, e.g. `30s`, `5m`.
+    #[arg(long, env = "OP_EXPORTER_COLLECT_INTERVAL", default_value = "1m", value_parser = parse_interval)]
+    collect_interval: Duration,
+
+    /// Collection interval overrides for specific metrics, e.g. `service-account=30s,item=6h`.
+    #[arg(long, env = "OP_EXPORTER_METRIC_INTERVAL", num_args = 1.., value_delimiter = ',', value_parser = parse_metric_interval)]
+    metric_interval: Vec<(Metrics, Duration)>,
+
+    /// Number of collection intervals without a successful collection after which `/readyz` reports not ready.
+    #[arg(
+        long,
+        env = "OP_EXPORTER_READINESS_STALE_INTERVALS",
+        default_value_t = 3,
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    readiness_stale_intervals: u32,
+
+    /// Time to wait for open connections to close, or the in-flight push to finish, on shutdown, after which they are closed forcibly.
+    #[arg(long, env = "OP_EXPORTER_SHUTDOWN_TIMEOUT", default_value = "10s", value_parser = humantime::parse_duration, global = true)]
+    shutdown_timeout: Duration,
+
+    /// Time since last update after which items are counted as stale, e.g. `90d,180d,365d`.
+    #[arg(long, env = "OP_EXPORTER_STALE_THRESHOLDS", num_args = 1.., value_delimiter = ',', default_values = ["90d", "180d", "365d"], global = true)]
+    stale_thresholds: Vec<StaleThreshold>,
+
+    /// Stale thresholds replacing `--stale-thresholds` for item categories, e.g. `API_CREDENTIAL=30d,API_CREDENTIAL=90d`.
+    #[arg(long, env = "OP_EXPORTER_STALE_CATEGORY_THRESHOLDS", num_args = 1.., value_delimiter = ',', value_parser = parse_category_threshold, global = true)]
+    stale_category_thresholds: Vec<(String, StaleThreshold)>,
+
+    /// Label items per last editor with user IDs only, not resolving names and emails from the user list.
+    #[arg(long, env = "OP_EXPORTER_EDITOR_IDS_ONLY", global = true)]
+    editor_ids_only: bool,
+
+    /// Path to web configuration file enabling TLS or basic authentication, in the Prometheus exporter-toolkit format.
+    #[arg(long, env = "OP_EXPORTER_WEB_CONFIG_FILE")]
+    web_config_file: Option<PathBuf>,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum Command {
+    /// Collect metrics once and print them, or write them to a file for the node_exporter textfile collector.
+    Collect(CollectArgs),
+    /// Collect metrics and push them to a Prometheus Pushgateway, instead of serving them.
+    Push(PushArgs),
+}
+
+/// Parse duration of an interval, rejecting zero which would make timers panic.
+pub(crate) fn parse_interval(s: &str) -> Result<Duration, String> {
+    let interval = humantime::parse_duration(s).map_err(|e| e.to_string())?;
+    if interval.is_zero() {
+        return Err("interval must be greater than zero".to_string());
+    }
+
+    Ok(interval)
 }
 
-async fn _main(args: Args) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    TermLogger::init(
-        args.log_level,
-        Config::default(),
-        TerminalMode::Mixed,
-        ColorChoice::Auto,
-    )?;
-    crate::server::run_server(
-        args.host,
-        args.port,
-        args.metrics,
-        args.op_path,
-        args.service_account_token,
-    )
-    .await
+/// Parse `<METRIC>=<DURATION>` pair of metric interval override.
+fn parse_metric_interval(s: &str) -> Result<(Metrics, Duration), String> {
+    let (metric, interval) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected `<METRIC>=<DURATION>`, got `{s}`"))?;
+    let metric = Metrics::from_str(metric, false)?;
+    let interval = parse_interval(interval)?;
+
+    Ok((metric, interval))
+}
+
+/// Parse `<CATEGORY>=<DURATION>` pair of stale threshold override.
+fn parse_category_threshold(s: &str) -> Result<(String, StaleThreshold), String> {
+    let (category, threshold) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected `<CATEGORY>=<DURATION>`, got `{s}`"))?;
+    let threshold = threshold
+        .parse::<StaleThreshold>()
+        .map_err(|e| e.to_string())?;
+
+    Ok((category.to_string(), threshold))
+}
+
+impl Args {
+    /// Fill options not given in command line with values from the configuration file.
+    fn merge_config(&mut self, config: &config::Config, matches: &ArgMatches) {
+        let explicit = |id: &str| {
+            !matches!(
+                matches.value_source(id),
+                None | Some(ValueSource::DefaultValue)
+            )
+        };
+        macro_rules! merge {
+            ($($field:ident),*) => {$(
+                if !explicit(stringify!($field)) {
+                    if let Some(value) = &config.$field {
+                        self.$field = value.clone();
+                    }
+                }
+            )*};
+        }
+        merge!(
+            log_level,
+            host,
+            port,
+            metrics,
+            op_path,
+            op_timeout,
+            op_concurrency,
+            collect_interval,
+            readiness_stale_intervals,
+            shutdown_timeout,
+            stale_thresholds,
+            editor_ids_only
+        );
+        if !explicit("web_config_file") && config.web_config_file.is_some() {
+            self.web_config_file = config.web_config_file.clone();
+        }
+        // Token and token file are alternatives, either given explicitly replaces both in the file
+        if !explicit("service_account_token") && !explicit("service_account_token_file") {
+            self.service_account_token = config.service_account_token.clone();
+            self.service_account_token_file = config.service_account_token_file.clone();
+        }
+
+        // Collector intervals of the file come first so `--metric-interval` of the same collector wins
+        let mut metric_interval: Vec<(Metrics, Duration)> = config
+            .collectors
+            .iter()
+            .filter_map(|(metric, collector)| collector.interval.map(|i| (*metric, i)))
+            .collect();
+        metric_interval.append(&mut self.metric_interval);
+        self.metric_interval = metric_interval;
+
+        // Categories overridden in command line replace those of the file as a whole
+        let mut stale_category_thresholds: Vec<(String, StaleThreshold)> = config
+            .stale_category_thresholds
+            .iter()
+            .filter(|(category, _)| {
+                !self
+                    .stale_category_thresholds
+                    .iter()
+                    .any(|(c, _)| c == *category)
+            })
+            .flat_map(|(category, thresholds)| {
+                thresholds.iter().map(|t| (category.clone(), t.clone()))
+            })
+            .collect();
+        stale_category_thresholds.append(&mut self.stale_category_thresholds);
+        self.stale_category_thresholds = stale_category_thresholds;
+    }
+
+    fn stale_thresholds(&self) -> StaleThresholds {
+        let mut per_category: HashMap<String, Vec<StaleThreshold>> = HashMap::new();
+        for (category, threshold) in &self.stale_category_thresholds {
+            per_category
+                .entry(category.clone())
+                .or_default()
+                .push(threshold.clone());
+        }
+
+        StaleThresholds {
+            default: self.stale_thresholds.clone(),
+            per_category,
+        }
+    }
+}
+
+fn read_token_file(path: &Path) -> Result<Secret, String> {
+    Secret::read(path).map_err(|err| {
+        format!(
+            "failed to read service account token file {}: {err}",
+            path.display()
+        )
+    })
+}
+
+/// Parse options from command line, environment variables and configuration file.
+fn load_args(matches: &ArgMatches) -> Result<(Args, config::Config), String> {
+    let mut args = Args::from_arg_matches(matches).map_err(|err| err.to_string())?;
+    let config = match &args.config {
+        Some(path) => {
+            config::Config::load(path).map_err(|err| format!("{}: {err}", path.display()))?
+        }
+        None => config::Config::default(),
+    };
+    args.merge_config(&config, matches);
+
+    Ok((args, config))
+}
+
+/// Service account token given directly or in a file, if any.
+fn service_account_token(args: &Args) -> Result<Option<Secret>, String> {
+    let service_account_token = match &args.service_account_token_file {
+        Some(path) => {
+            log::info!("Reading service account token from {}", path.display());
+            Some(read_token_file(path)?)
+        }
+        None => args.service_account_token.clone(),
+    };
+    if service_account_token.is_some() {
+        log::warn!("Service account token explicitly set.");
+    }
+
+    Ok(service_account_token)
+}
+
+/// Create collector of the default instance, not labeled by target.
+fn default_metrics_collector(
+    args: &Args,
+    service_account_token: Option<Secret>,
+) -> OpMetricsCollector {
+    let command_executor = OpCommandExecutor::new(
+        args.op_path.clone(),
+        service_account_token.map(Secret::into_inner),
+        args.op_timeout,
+        args.op_concurrency,
+    );
+
+    OpMetricsCollector::new(Box::new(command_executor))
+        .with_stale_thresholds(args.stale_thresholds())
+        .with_editor_ids_only(args.editor_ids_only)
+}
+
+/// Create collectors of the default instance and targets, and the server state serving them.
+fn build_state(
+    args: &Args,
+    config: config::Config,
+) -> Result<ServerState, Box<dyn std::error::Error + Send + Sync>> {
+    log::info!("Enabled metrics: {:?}", args.metrics);
+    log::info!("Using 1Password CLI: {}", args.op_path);
+    let service_account_token = service_account_token(args)?;
+    let intervals = HashMap::from_iter(args.metric_interval.iter().copied());
+
+    // Each target has its own 1Password CLI and registry, with all metrics labeled by target name
+    let mut targets = HashMap::new();
+    for (name, target) in config.targets {
+        log::info!("Configured target: {}", name);
+        let token = match &target.service_account_token_file {
+            Some(path) => Some(read_token_file(path)?),
+            None => target.service_account_token,
+        };
+        let command_executor = OpCommandExecutor::new(
+            target.op_path.unwrap_or_else(|| args.op_path.clone()),
+            token
+                .or_else(|| service_account_token.clone())
+                .map(Secret::into_inner),
+            args.op_timeout,
+            args.op_concurrency,
+        );
+        let registry = Registry::new_custom(
+            None,
+            Some(HashMap::from([("target".to_string(), name.clone())])),
+        )?;
+        let metrics_collector =
+            OpMetricsCollector::with_registry(Box::new(command_executor), registry)
+                .with_stale_thresholds(args.stale_thresholds())
+                .with_editor_ids_only(args.editor_ids_only);
+        let scheduler = Scheduler::new(
+            metrics_collector,
+            target.metrics.unwrap_or_else(|| args.metrics.clone()),
+            args.collect_interval,
+            &intervals,
+        );
+        targets.insert(name, Arc::new(scheduler));
+    }
+
+    // Without a global token, the default instance of a configuration with targets would only fail to authenticate.
+    // `OP_SERVICE_ACCOUNT_TOKEN` of the exporter process, inherited by the 1Password CLI, counts as one.
+    let inherited_token = std::env::var_os("OP_SERVICE_ACCOUNT_TOKEN").is_some();
+    let scheduler = if targets.is_empty() || service_account_token.is_some() || inherited_token {
+        let metrics_collector = default_metrics_collector(args, service_account_token);
+        Some(Arc::new(Scheduler::new(
+            metrics_collector,
+            args.metrics.clone(),
+            args.collect_interval,
+            &intervals,
+        )))
+    } else {
+        log::warn!(
+            "No global service account token nor OP_SERVICE_ACCOUNT_TOKEN set, collecting targets only and /metrics responds 404"
+        );
+        None
+    };
+
+    let web_config = match &args.web_config_file {
+        Some(path) => WebConfig::load(path).map_err(|err| format!("{}: {err}", path.display()))?,
+        None => WebConfig::default(),
+    };
+
+    Ok(ServerState {
+        scheduler,
+        targets,
+        op_path: args.op_path.clone(),
+        readiness_stale_intervals: args.readiness_stale_intervals,
+        web_config,
+    })
+}
+
+fn init_logger(log_level: LevelFilter, mode: TerminalMode) -> Result<(), log::SetLoggerError> {
+    TermLogger::init(log_level, Config::default(), mode, ColorChoice::Auto)
+}
+
+async fn _main(
+    args: Args,
+    config: config::Config,
+    reload_args: impl Fn() -> Result<(Args, config::Config), String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    // Let the logger pass everything, so the level can be changed on reload through the global max level
+    init_logger(LevelFilter::Trace, TerminalMode::Mixed)?;
+    log::set_max_level(args.log_level);
+
+    let state = build_state(&args, config)?;
+    let reload = || {
+        let (new_args, config) = reload_args()?;
+        log::set_max_level(new_args.log_level);
+        if (&new_args.host, new_args.port) != (&args.host, args.port) {
+            log::warn!(
+                "Changing host or port requires restart, keep listening on {}:{}",
+                args.host,
+                args.port
+            );
+        }
+        build_state(&new_args, config)
+    };
+
+    crate::server::run_server(&args.host, args.port, state, args.shutdown_timeout, reload).await
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let args = Args::parse();
-    _main(args).await
+    let matches = Args::command().get_matches();
+    let (args, config) = load_args(&matches)
+        .unwrap_or_else(|err| Args::command().error(ErrorKind::InvalidValue, err).exit());
+
+    // One-shot modes exit with status code telling what failed
+    match &args.command {
+        Some(Command::Collect(collect_args)) => {
+            // Keep stdout for the metrics
+            init_logger(args.log_level, TerminalMode::Stderr)?;
+            let metrics_collector = default_metrics_collector(&args, service_account_token(&args)?);
+            let result =
+                crate::collect::run(metrics_collector, args.metrics.clone(), collect_args).await;
+            if let Err(err) = result {
+                log::error!("{}", err);
+                std::process::exit(err.exit_code());
+            }
+            return Ok(());
+        }
+        Some(Command::Push(push_args)) => {
+            init_logger(args.log_level, TerminalMode::Mixed)?;
+            log::info!("Enabled metrics: {:?}", args.metrics);
+            let metrics_collector = default_metrics_collector(&args, service_account_token(&args)?);
+            let result = crate::push::run(
+                metrics_collector,
+                args.metrics.clone(),
+                push_args,
+                args.shutdown_timeout,
+            )
+            .await;
+            if let Err(err) = result {
+                log::error!("{}", err);
+                std::process::exit(err.exit_code());
+            }
+            return Ok(());
+        }
+        None => {}
+    }
+
+    _main(args, config, || load_args(&matches)).await
 }
 
 #[cfg(test)]
 mod tests {
+    use std::io::Read;
+
     use super::*;
 
     #[tokio::test]
@@ -75,11 +472,14 @@ async fn test_metrics_serving() {
         let port = test_helper::get_random_port();
         let server = tokio::spawn(async move {
             let args = Args {
+                command: None,
+                config: None,
                 log_level: LevelFilter::Debug,
                 host: "127.0.0.1".to_string(),
                 port,
                 op_path: test_helper::MOCK_OP.to_string(),
-                service_account_token: Some("ops_blahblah".to_string()),
+                service_account_token: Some("ops_blahblah".parse().unwrap()),
+                service_account_token_file: None,
                 metrics: vec![
                     Metrics::Account,
                     Metrics::BuildInfo,
@@ -90,8 +490,26 @@ async fn test_metrics_serving() {
                     Metrics::User,
                     Metrics::Vault,
                 ],
+                op_timeout: Duration::from_secs(10),
+                op_concurrency: 4,
+                collect_interval: Duration::from_secs(60),
+                metric_interval: vec![],
+                readiness_stale_intervals: 3,
+                shutdown_timeout: Duration::from_secs(10),
+                web_config_file: None,
+                stale_thresholds: vec![
+                    "90d".parse().unwrap(),
+                    "180d".parse().unwrap(),
+                    "365d".parse().unwrap(),
+                ],
+                stale_category_thresholds: vec![],
+                editor_ids_only: false,
             };
-            _main(args).await.unwrap();
+            _main(args, config::Config::default(), || {
+                Err("reload not supported".to_string())
+            })
+            .await
+            .unwrap();
         });
 
         // Wait for the server to start
@@ -104,8 +522,241 @@ async fn test_metrics_serving() {
             .await
             .unwrap();
 
-        assert_eq!(body, include_str!(test_dir!("expected_metrics.txt")));
+        assert_eq!(
+            test_helper::strip_volatile_metrics(&body),
+            include_str!(test_dir!("expected_metrics.txt"))
+        );
+
+        let filtered = reqwest::get(format!("http://localhost:{port}/metrics?collect[]=vault"))
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        assert!(filtered.contains("op_vault_count_total"));
+        assert!(!filtered.contains("op_item_count_total"));
+
+        let openmetrics = reqwest::Client::new()
+            .get(format!("http://localhost:{port}/metrics"))
+            .header("Accept", "application/openmetrics-text;version=1.0.0")
+            .header("Accept-Encoding", "gzip")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(
+            openmetrics.headers()["Content-Type"],
+            crate::openmetrics::CONTENT_TYPE
+        );
+        assert_eq!(openmetrics.headers()["Content-Encoding"], "gzip");
+        let mut decoded = String::new();
+        flate2::read::GzDecoder::new(&openmetrics.bytes().await.unwrap()[..])
+            .read_to_string(&mut decoded)
+            .unwrap();
+        assert!(decoded.contains("# TYPE op_vault_count_total gauge"));
+        assert!(decoded.ends_with("# EOF\n"));
+
+        let snapshot = reqwest::get(format!("http://localhost:{port}/api/v1/snapshot"))
+            .await
+            .unwrap();
+        assert_eq!(snapshot.status(), 200);
+        let snapshot: serde_json::Value =
+            serde_json::from_str(&snapshot.text().await.unwrap()).unwrap();
+        assert_eq!(snapshot["vaults"][0]["name"], "Testing 金庫");
+        assert_eq!(snapshot["vaults"][0]["item_count"], 4);
+        assert_eq!(snapshot["items"]["per_category"]["LOGIN"], 1);
+        assert_eq!(snapshot["items"]["archived"], 1);
+        assert_eq!(snapshot["whoami"]["user_type"], "SERVICE_ACCOUNT");
+
+        let landing_page = reqwest::get(format!("http://localhost:{port}/"))
+            .await
+            .unwrap();
+        assert_eq!(landing_page.status(), 200);
+        assert!(landing_page
+            .text()
+            .await
+            .unwrap()
+            .contains("1Password Exporter"));
+
+        let healthz = reqwest::get(format!("http://localhost:{port}/healthz"))
+            .await
+            .unwrap();
+        assert_eq!(healthz.status(), 200);
+
+        let readyz = reqwest::get(format!("http://localhost:{port}/readyz"))
+            .await
+            .unwrap();
+        assert_eq!(readyz.status(), 200);
+        let readyz: serde_json::Value =
+            serde_json::from_str(&readyz.text().await.unwrap()).unwrap();
+        assert_eq!(readyz["status"], "ok");
 
         server.abort();
     }
+
+    #[test]
+    fn test_args_debug_redacts_token() {
+        let args = Args::parse_from([
+            "onepassword-exporter",
+            "--service-account-token",
+            "ops_secret",
+        ]);
+
+        assert!(!format!("{args:?}").contains("ops_secret"));
+    }
+
+    #[test]
+    fn test_zero_collect_interval() {
+        let err =
+            Args::try_parse_from(["onepassword-exporter", "--collect-interval", "0s"]).unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::ValueValidation);
+        assert!(Args::try_parse_from(["onepassword-exporter", "--collect-interval", "1s"]).is_ok());
+    }
+
+    #[test]
+    fn test_zero_op_timeout() {
+        let err = Args::try_parse_from(["onepassword-exporter", "--op-timeout", "0s"]).unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::ValueValidation);
+        assert!(Args::try_parse_from(["onepassword-exporter", "--op-timeout", "1s"]).is_ok());
+    }
+
+    #[test]
+    fn test_zero_op_concurrency() {
+        let err =
+            Args::try_parse_from(["onepassword-exporter", "--op-concurrency", "0"]).unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::ValueValidation);
+        assert!(Args::try_parse_from(["onepassword-exporter", "--op-concurrency", "1"]).is_ok());
+    }
+
+    #[test]
+    fn test_zero_readiness_stale_intervals() {
+        let err =
+            Args::try_parse_from(["onepassword-exporter", "--readiness-stale-intervals", "0"])
+                .unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::ValueValidation);
+        assert!(
+            Args::try_parse_from(["onepassword-exporter", "--readiness-stale-intervals", "1"])
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_global_options_after_subcommand() {
+        let matches = Args::command().get_matches_from([
+            "onepassword-exporter",
+            "collect",
+            "--metrics",
+            "group",
+            "--op-path",
+            "/usr/local/bin/op",
+        ]);
+        let args = Args::from_arg_matches(&matches).unwrap();
+
+        assert!(matches!(args.command, Some(Command::Collect(_))));
+        assert_eq!(args.metrics, vec![Metrics::Group]);
+        assert_eq!(args.op_path, "/usr/local/bin/op");
+        // Seen as given explicitly, so not replaced by the configuration file
+        assert_eq!(
+            matches.value_source("metrics"),
+            Some(ValueSource::CommandLine)
+        );
+
+        let args = Args::try_parse_from([
+            "onepassword-exporter",
+            "push",
+            "--pushgateway-url",
+            "http://localhost:9091",
+            "--metrics",
+            "item",
+        ])
+        .unwrap();
+        assert!(matches!(args.command, Some(Command::Push(_))));
+        assert_eq!(args.metrics, vec![Metrics::Item]);
+    }
+
+    #[test]
+    fn test_merge_config() {
+        // Arrange
+        let matches = Args::command().get_matches_from([
+            "onepassword-exporter",
+            "--port",
+            "9100",
+            "--metric-interval",
+            "item=1h",
+            "--stale-category-thresholds",
+            "API_CREDENTIAL=7d",
+        ]);
+        let mut args = Args::from_arg_matches(&matches).unwrap();
+        let config = config::Config {
+            host: Some("0.0.0.0".to_string()),
+            port: Some(9200),
+            collectors: HashMap::from([
+                (
+                    Metrics::Item,
+                    config::CollectorConfig {
+                        interval: Some(Duration::from_secs(6 * 60 * 60)),
+                    },
+                ),
+                (
+                    Metrics::Document,
+                    config::CollectorConfig {
+                        interval: Some(Duration::from_secs(6 * 60 * 60)),
+                    },
+                ),
+            ]),
+            stale_thresholds: Some(vec!["1d".parse().unwrap()]),
+            stale_category_thresholds: HashMap::from([
+                ("API_CREDENTIAL".to_string(), vec!["30d".parse().unwrap()]),
+                ("SECURE_NOTE".to_string(), vec!["1y".parse().unwrap()]),
+            ]),
+            editor_ids_only: Some(true),
+            ..Default::default()
+        };
+
+        // Act
+        args.merge_config(&config, &matches);
+
+        // Assert
+        assert_eq!(args.host, "0.0.0.0");
+        assert_eq!(args.port, 9100);
+        assert_eq!(args.op_path, "op");
+        assert!(args.editor_ids_only);
+        let intervals: HashMap<Metrics, Duration> =
+            HashMap::from_iter(args.metric_interval.clone());
+        assert_eq!(intervals[&Metrics::Item], Duration::from_secs(60 * 60));
+        assert_eq!(
+            intervals[&Metrics::Document],
+            Duration::from_secs(6 * 60 * 60)
+        );
+        assert_eq!(
+            args.stale_thresholds(),
+            StaleThresholds {
+                default: vec!["1d".parse().unwrap()],
+                per_category: HashMap::from([
+                    ("API_CREDENTIAL".to_string(), vec!["7d".parse().unwrap()]),
+                    ("SECURE_NOTE".to_string(), vec!["1y".parse().unwrap()]),
+                ]),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_metric_interval() {
+        assert_eq!(
+            parse_metric_interval("service-account=30s"),
+            Ok((Metrics::ServiceAccount, Duration::from_secs(30)))
+        );
+        assert_eq!(
+            parse_metric_interval("item=6h"),
+            Ok((Metrics::Item, Duration::from_secs(6 * 60 * 60)))
+        );
+
+        assert!(parse_metric_interval("item").is_err());
+        assert!(parse_metric_interval("unknown=1m").is_err());
+        assert!(parse_metric_interval("item=forever").is_err());
+        assert!(parse_metric_interval("item=0s").is_err());
+    }
 }
@@ -1,17 +1,50 @@
-use std::str::FromStr;
+use std::{collections::HashMap,
+          fmt,
+          str::FromStr,
+          sync::RwLock,
+          time::{Duration, Instant}};
 
-use crate::command_executor::CommandExecutor;
+use futures::future::join_all;
+use prometheus::{proto::MetricFamily, Registry};
+
+use crate::command_executor::{CommandExecutor, OpError};
 
 mod account;
 mod build_info;
 mod document;
+mod exporter;
 mod group;
 mod item;
 mod service_account;
+mod snapshot;
 mod user;
 mod vault;
 
-#[derive(Copy, Clone, Debug, PartialEq, clap::ValueEnum)]
+pub(crate) use build_info::VERSION;
+pub(crate) use item::{StaleThreshold, StaleThresholds};
+pub(crate) use snapshot::Snapshot;
+
+/// `state` label value of items and documents in use.
+const ACTIVE: &str = "active";
+/// `state` label value of archived items and documents.
+const ARCHIVED: &str = "archived";
+
+/// How long results of the 1Password CLI checks are reused, so probes do not spawn the CLI on every request.
+const OP_CHECKS_TTL: Duration = Duration::from_secs(30);
+
+/// How long the user list is reused to resolve editors of items, so collecting items does not list users each time.
+const USERS_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// Returns `state` label value of an item or document, given its state in the 1Password CLI output.
+fn state_label(state: Option<&str>) -> &'static str {
+    match state {
+        Some("ARCHIVED") => ARCHIVED,
+        _ => ACTIVE,
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, clap::ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub(crate) enum Metrics {
     // Metrics that does not consume quota
     Account,
@@ -43,38 +76,308 @@ fn from_str(input: &str) -> Result<Self, Self::Err> {
     }
 }
 
+impl fmt::Display for Metrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Metrics::Account => "account",
+            Metrics::BuildInfo => "build-info",
+            Metrics::Document => "document",
+            Metrics::Group => "group",
+            Metrics::Item => "item",
+            Metrics::ServiceAccount => "service-account",
+            Metrics::User => "user",
+            Metrics::Vault => "vault",
+        };
+        write!(f, "{s}")
+    }
+}
+
+impl Metrics {
+    /// Prefix of names of the metric families this collector produces.
+    fn family_prefix(&self) -> &'static str {
+        match self {
+            Metrics::Account => "op_account_",
+            Metrics::BuildInfo => "op_exporter_buildinfo",
+            Metrics::Document => "op_document_",
+            Metrics::Group => "op_group_",
+            Metrics::Item => "op_item_",
+            Metrics::ServiceAccount => "op_serviceaccount_",
+            Metrics::User => "op_user_",
+            Metrics::Vault => "op_vault_",
+        }
+    }
+}
+
+/// Keep only metric families produced by given collectors.
+///
+/// Exporter metrics labeled by `collector` are narrowed down to the given collectors, other metrics not owned by any collector are kept as is.
+pub(crate) fn filter_metric_families(
+    metric_families: Vec<MetricFamily>,
+    metrics: &[Metrics],
+) -> Vec<MetricFamily> {
+    let collectors: Vec<String> = metrics.iter().map(Metrics::to_string).collect();
+    metric_families
+        .into_iter()
+        .filter_map(|mut mf| {
+            let owner = <Metrics as clap::ValueEnum>::value_variants()
+                .iter()
+                .find(|m| mf.name().starts_with(m.family_prefix()));
+            if let Some(owner) = owner {
+                return metrics.contains(owner).then_some(mf);
+            }
+
+            // Exporter metrics, such as `op_exporter_collector_success`
+            mf.mut_metric().retain(|m| {
+                m.get_label()
+                    .iter()
+                    .find(|l| l.name() == "collector")
+                    .is_none_or(|l| collectors.iter().any(|c| c == l.value()))
+            });
+            (!mf.get_metric().is_empty()).then_some(mf)
+        })
+        .collect()
+}
+
+/// Replace metric families produced by given collectors in `previous` with those freshly `gathered`.
+///
+/// Families of other collectors are kept from `previous`, so metrics of a collection running meanwhile, possibly reset
+/// but not yet set, are not published.
+pub(crate) fn merge_metric_families(
+    previous: Vec<MetricFamily>,
+    gathered: Vec<MetricFamily>,
+    metrics: &[Metrics],
+) -> Vec<MetricFamily> {
+    let others: Vec<Metrics> = <Metrics as clap::ValueEnum>::value_variants()
+        .iter()
+        .filter(|m| !metrics.contains(m))
+        .copied()
+        .collect();
+
+    let mut merged = filter_metric_families(gathered, metrics);
+    for mut mf in filter_metric_families(previous, &others) {
+        match merged.iter_mut().find(|m| m.name() == mf.name()) {
+            // Exporter metrics, keeping those of other collectors only as the rest is fresh
+            Some(fresh) => {
+                let kept = mf
+                    .take_metric()
+                    .into_iter()
+                    .filter(|m| m.get_label().iter().any(|l| l.name() == "collector"));
+                fresh.mut_metric().extend(kept);
+                // Sorted by label values as gathered by the registry
+                fresh.mut_metric().sort_by(|m1, m2| {
+                    let values = |m: &prometheus::proto::Metric| {
+                        m.get_label()
+                            .iter()
+                            .map(|l| l.value().to_string())
+                            .collect::<Vec<_>>()
+                    };
+                    values(m1).cmp(&values(m2))
+                });
+            }
+            None => merged.push(mf),
+        }
+    }
+    merged.sort_by(|mf1, mf2| mf1.name().cmp(mf2.name()));
+    merged
+}
+
+/// Error occurred while collecting metrics.
+#[derive(Debug)]
+pub(crate) enum CollectError {
+    /// Failed to execute the 1Password CLI.
+    Exec(OpError),
+    /// Failed to parse output of the 1Password CLI.
+    Parse(serde_json::Error),
+}
+
+impl CollectError {
+    /// Short name of the error kind, used as metric label.
+    pub(crate) fn kind(&self) -> &'static str {
+        match self {
+            CollectError::Exec(err) => err.kind(),
+            CollectError::Parse(_) => "parse",
+        }
+    }
+}
+
+impl fmt::Display for CollectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CollectError::Exec(err) => write!(f, "1Password CLI failed: {err}"),
+            CollectError::Parse(err) => write!(f, "failed to parse 1Password CLI output: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for CollectError {}
+
+impl From<OpError> for CollectError {
+    fn from(err: OpError) -> Self {
+        CollectError::Exec(err)
+    }
+}
+
+impl From<serde_json::Error> for CollectError {
+    fn from(err: serde_json::Error) -> Self {
+        CollectError::Parse(err)
+    }
+}
+
+/// Results of the 1Password CLI checks, with errors rendered as messages.
+#[derive(Clone, Debug)]
+pub(crate) struct OpChecks {
+    /// Version of the 1Password CLI.
+    pub(crate) version: Result<String, String>,
+    /// Whether the 1Password CLI is authenticated.
+    pub(crate) auth: Result<(), String>,
+}
+
 pub(crate) struct OpMetricsCollector {
     command_executor: Box<dyn CommandExecutor>,
+    registry: Registry,
+    account: account::AccountMetrics,
+    build_info: build_info::BuildInfoMetrics,
+    document: document::DocumentMetrics,
+    exporter: exporter::ExporterMetrics,
+    group: group::GroupMetrics,
+    item: item::ItemMetrics,
+    service_account: service_account::ServiceAccountMetrics,
+    user: user::UserMetrics,
+    vault: vault::VaultMetrics,
+    snapshot: RwLock<Snapshot>,
+    /// Users by ID as last listed and when, to resolve editors of items.
+    users: tokio::sync::Mutex<Option<(Instant, HashMap<String, user::User>)>>,
+    /// Last results of the 1Password CLI checks and when they ran.
+    op_checks: tokio::sync::Mutex<Option<(Instant, OpChecks)>>,
+    stale_thresholds: StaleThresholds,
+    editor_ids_only: bool,
 }
 
 impl OpMetricsCollector {
     pub(crate) fn new(command_executor: Box<dyn CommandExecutor>) -> Self {
-        OpMetricsCollector { command_executor }
-    }
-
-    pub(crate) fn collect(&self, metrics: Vec<Metrics>) {
-        // TODO: Collect all metrics in async manner (use Tokio)
-        for metric in metrics {
-            match metric {
-                Metrics::Account => self.read_account(),
-                Metrics::BuildInfo => self.read_buildinfo(),
-                Metrics::Document => self.read_document(),
-                Metrics::Group => self.read_group(),
-                Metrics::Item => self.read_item(),
-                Metrics::ServiceAccount => {
-                    self.read_ratelimit();
-                    self.read_whoami();
-                }
-                Metrics::User => self.read_user(),
-                Metrics::Vault => self.read_vault(),
+        Self::with_registry(command_executor, Registry::new())
+    }
+
+    /// Create a collector registering its metrics to given registry, which should be empty.
+    pub(crate) fn with_registry(
+        command_executor: Box<dyn CommandExecutor>,
+        registry: Registry,
+    ) -> Self {
+        // Metric names are fixed and registered to an empty registry, so this cannot fail
+        OpMetricsCollector {
+            command_executor,
+            account: account::AccountMetrics::new(&registry).unwrap(),
+            build_info: build_info::BuildInfoMetrics::new(&registry).unwrap(),
+            document: document::DocumentMetrics::new(&registry).unwrap(),
+            exporter: exporter::ExporterMetrics::new(&registry).unwrap(),
+            group: group::GroupMetrics::new(&registry).unwrap(),
+            item: item::ItemMetrics::new(&registry).unwrap(),
+            service_account: service_account::ServiceAccountMetrics::new(&registry).unwrap(),
+            user: user::UserMetrics::new(&registry).unwrap(),
+            vault: vault::VaultMetrics::new(&registry).unwrap(),
+            snapshot: RwLock::new(Snapshot::default()),
+            users: tokio::sync::Mutex::new(None),
+            op_checks: tokio::sync::Mutex::new(None),
+            stale_thresholds: StaleThresholds::default(),
+            editor_ids_only: false,
+            registry,
+        }
+    }
+
+    /// Registry holding all metrics of this collector.
+    pub(crate) fn registry(&self) -> &Registry {
+        &self.registry
+    }
+
+    /// Run the 1Password CLI checks, reusing results of the last run within `OP_CHECKS_TTL`.
+    ///
+    /// The checks do not wait for the `--op-concurrency` limit, so probes are not held up by long collections.
+    pub(crate) async fn op_checks(&self) -> OpChecks {
+        // Held across the checks so concurrent callers wait for one run instead of starting their own
+        let mut cached = self.op_checks.lock().await;
+        if let Some((checked_at, checks)) = &*cached {
+            if checked_at.elapsed() < OP_CHECKS_TTL {
+                return checks.clone();
+            }
+        }
+
+        let (version, auth) = tokio::join!(self.op_version(), self.check_auth());
+        let checks = OpChecks {
+            version: version.map_err(|err| err.to_string()),
+            auth: auth.map_err(|err| err.to_string()),
+        };
+        *cached = Some((Instant::now(), checks.clone()));
+        checks
+    }
+
+    /// Returns version of the 1Password CLI.
+    async fn op_version(&self) -> Result<String, OpError> {
+        let output = self
+            .command_executor
+            .exec_unqueued(vec!["--version"])
+            .await?;
+        Ok(output.trim().to_string())
+    }
+
+    /// Check the 1Password CLI is authenticated, without consuming API rate.
+    async fn check_auth(&self) -> Result<(), OpError> {
+        self.command_executor
+            .exec_unqueued(vec!["whoami", "--format", "json"])
+            .await?;
+        Ok(())
+    }
+
+    /// Collect given metrics concurrently, returning the result of each. Failure of one does not affect others.
+    pub(crate) async fn collect(
+        &self,
+        metrics: Vec<Metrics>,
+    ) -> Vec<(Metrics, Result<(), CollectError>)> {
+        let results = join_all(metrics.into_iter().map(|metric| async move {
+            let start = Instant::now();
+            let result = self.collect_one(metric).await;
+            self.record_collection(metric, start.elapsed(), &result);
+            if let Err(err) = &result {
+                log::error!("Failed to collect {} metrics: {}", metric, err);
             }
+            (metric, result)
+        }))
+        .await;
+        self.mark_collection_done();
+
+        results
+    }
+
+    async fn collect_one(&self, metric: Metrics) -> Result<(), CollectError> {
+        match metric {
+            Metrics::Account => self.read_account().await,
+            Metrics::BuildInfo => {
+                self.read_buildinfo();
+                Ok(())
+            }
+            Metrics::Document => self.read_document().await,
+            Metrics::Group => self.read_group().await,
+            Metrics::Item => self.read_item().await,
+            Metrics::ServiceAccount => {
+                // Read whoami even if ratelimit failed
+                let (ratelimit, whoami) = tokio::join!(self.read_ratelimit(), self.read_whoami());
+                ratelimit.and(whoami)
+            }
+            Metrics::User => self.read_user().await,
+            Metrics::Vault => self.read_vault().await,
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
+
+    use mockall::predicate::*;
+    use rstest::*;
+
     use super::*;
+    use crate::{command_executor::{MockCommandExecutor, OpErrorKind},
+                testing::{command_executor, metrics_collector}};
 
     #[test]
     fn test_metrics_from_str() {
@@ -92,4 +395,232 @@ fn test_metrics_from_str() {
 
         assert_eq!(Metrics::from_str("unknown"), Err(()));
     }
+
+    #[test]
+    fn test_metrics_display() {
+        for metric in <Metrics as clap::ValueEnum>::value_variants() {
+            assert_eq!(Metrics::from_str(&metric.to_string()), Ok(*metric));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_collect_failure() {
+        // Arrange
+        let mut command_executor = MockCommandExecutor::new();
+        command_executor
+            .expect_exec()
+            .with(eq(vec!["group", "list", "--format", "json"]))
+            .returning(|_| Ok("not a json".to_string()));
+        command_executor
+            .expect_exec()
+            .with(eq(vec!["user", "list", "--format", "json"]))
+            .returning(|_| {
+                Err(OpError::Failed {
+                    kind: OpErrorKind::Authentication,
+                    exit_code: Some(1),
+                    stderr: "You are not currently signed in.".to_string(),
+                })
+            });
+        let metrics_collector = OpMetricsCollector::new(Box::new(command_executor));
+
+        // Act
+        let results = metrics_collector
+            .collect(vec![Metrics::Group, Metrics::User, Metrics::BuildInfo])
+            .await;
+
+        // Assert
+        assert!(matches!(
+            results[0],
+            (Metrics::Group, Err(CollectError::Parse(_)))
+        ));
+        assert!(
+            matches!(results[1], (Metrics::User, Err(ref err)) if err.kind() == "authentication")
+        );
+        assert!(matches!(results[2], (Metrics::BuildInfo, Ok(()))));
+
+        let names: Vec<String> = metrics_collector
+            .registry()
+            .gather()
+            .iter()
+            .map(|mf| mf.name().to_string())
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "op_exporter_buildinfo",
+                "op_exporter_collector_duration_seconds",
+                "op_exporter_collector_errors_total",
+                "op_exporter_collector_success",
+                "op_exporter_last_collection_timestamp_seconds",
+            ]
+        );
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_filter_metric_families(metrics_collector: OpMetricsCollector) {
+        // Arrange
+        metrics_collector
+            .collect(vec![Metrics::Group, Metrics::Vault, Metrics::BuildInfo])
+            .await;
+
+        // Act
+        let metric_families =
+            filter_metric_families(metrics_collector.registry().gather(), &[Metrics::Group]);
+
+        // Assert
+        let names: Vec<String> = metric_families
+            .iter()
+            .map(|mf| mf.name().to_string())
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "op_exporter_collector_duration_seconds",
+                "op_exporter_collector_success",
+                "op_exporter_last_collection_timestamp_seconds",
+                "op_group_count_total",
+            ]
+        );
+        let collectors: Vec<&str> = metric_families[1]
+            .get_metric()
+            .iter()
+            .map(|m| m.get_label()[0].value())
+            .collect();
+        assert_eq!(collectors, vec!["group"]);
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_collect_with_registry(command_executor: MockCommandExecutor) {
+        // Arrange
+        let registry = Registry::new_custom(
+            None,
+            Some(HashMap::from([(
+                "target".to_string(),
+                "family".to_string(),
+            )])),
+        )
+        .unwrap();
+        let metrics_collector =
+            OpMetricsCollector::with_registry(Box::new(command_executor), registry);
+
+        // Act
+        metrics_collector.collect(vec![Metrics::Group]).await;
+
+        // Assert
+        let metric_families = metrics_collector.registry().gather();
+        assert!(metric_families
+            .iter()
+            .all(|mf| mf.get_metric().iter().all(|m| m
+                .get_label()
+                .iter()
+                .any(|l| l.name() == "target" && l.value() == "family"))));
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_collect_only_enabled(metrics_collector: OpMetricsCollector) {
+        // Act
+        let results = metrics_collector
+            .collect(vec![Metrics::Group, Metrics::Vault])
+            .await;
+
+        // Assert
+        assert!(results.iter().all(|(_, result)| result.is_ok()));
+        let names: Vec<String> = metrics_collector
+            .registry()
+            .gather()
+            .iter()
+            .map(|mf| mf.name().to_string())
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "op_exporter_collector_duration_seconds",
+                "op_exporter_collector_success",
+                "op_exporter_last_collection_timestamp_seconds",
+                "op_group_count_total",
+                "op_vault_count_total",
+                "op_vault_info",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_collect_error_kind() {
+        let failed = |kind| {
+            CollectError::Exec(OpError::Failed {
+                kind,
+                exit_code: Some(1),
+                stderr: String::new(),
+            })
+        };
+        let errors = [
+            failed(OpErrorKind::Authentication),
+            failed(OpErrorKind::RateLimited),
+            failed(OpErrorKind::PermissionDenied),
+            failed(OpErrorKind::NotFound),
+            failed(OpErrorKind::Unknown),
+            CollectError::Exec(OpError::Io(std::io::ErrorKind::NotFound.into())),
+            CollectError::Exec(OpError::Timeout(Duration::from_secs(30))),
+            CollectError::Parse(serde_json::from_str::<()>("").unwrap_err()),
+        ];
+
+        // Kinds documented in README.md as values of the `kind` label
+        assert_eq!(
+            errors.iter().map(CollectError::kind).collect::<Vec<_>>(),
+            vec![
+                "authentication",
+                "rate_limited",
+                "permission_denied",
+                "not_found",
+                "unknown",
+                "io",
+                "timeout",
+                "parse",
+            ]
+        );
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_merge_metric_families(metrics_collector: OpMetricsCollector) {
+        // Arrange
+        metrics_collector
+            .collect(vec![Metrics::Group, Metrics::Vault])
+            .await;
+        let previous = metrics_collector.registry().gather();
+        // As gathered while `group` is reset by another collection
+        let resetting = OpMetricsCollector::new(Box::new(command_executor()));
+        resetting.collect(vec![Metrics::Vault]).await;
+
+        // Act
+        let merged =
+            merge_metric_families(previous, resetting.registry().gather(), &[Metrics::Vault]);
+
+        // Assert
+        let names: Vec<&str> = merged.iter().map(|mf| mf.name()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "op_exporter_collector_duration_seconds",
+                "op_exporter_collector_success",
+                "op_exporter_last_collection_timestamp_seconds",
+                "op_group_count_total",
+                "op_vault_count_total",
+                "op_vault_info",
+            ]
+        );
+        let success = merged
+            .iter()
+            .find(|mf| mf.name() == "op_exporter_collector_success")
+            .unwrap();
+        let collectors: Vec<&str> = success
+            .get_metric()
+            .iter()
+            .map(|m| m.get_label()[0].value())
+            .collect();
+        assert_eq!(collectors, vec!["group", "vault"]);
+    }
 }
@@ -1,22 +1,30 @@
-use lazy_static::lazy_static;
 #[cfg(test)]
 use mockall::predicate::*;
-use prometheus::{register_int_gauge_vec, IntGaugeVec};
-use serde::Deserialize;
+use prometheus::{register_int_gauge_vec_with_registry, IntGaugeVec, Registry};
+use serde::{Deserialize, Serialize};
 
-use super::OpMetricsCollector;
+use super::{CollectError, OpMetricsCollector};
 
-lazy_static! {
-    static ref OP_ACCOUNT_CURRENT: IntGaugeVec = register_int_gauge_vec!(
-        "op_account_current",
-        "Current 1Password account information.",
-        &["id", "name", "domain", "type", "state", "created_at"]
-    )
-    .unwrap();
+/// Metrics of the current 1Password account.
+pub(super) struct AccountMetrics {
+    current: IntGaugeVec,
 }
 
-#[derive(Deserialize, Debug)]
-struct Account {
+impl AccountMetrics {
+    pub(super) fn new(registry: &Registry) -> prometheus::Result<Self> {
+        Ok(AccountMetrics {
+            current: register_int_gauge_vec_with_registry!(
+                "op_account_current",
+                "Current 1Password account information.",
+                &["id", "name", "domain", "type", "state", "created_at"],
+                registry
+            )?,
+        })
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub(super) struct Account {
     id: String,
     name: String,
     domain: String,
@@ -27,14 +35,16 @@ struct Account {
 }
 
 impl OpMetricsCollector {
-    pub(crate) fn read_account(&self) {
+    pub(crate) async fn read_account(&self) -> Result<(), CollectError> {
         let output = self
             .command_executor
             .exec(vec!["account", "get", "--format", "json"])
-            .unwrap();
-        let account: Account = serde_json::from_str(&output).unwrap();
+            .await?;
+        let account: Account = serde_json::from_str(&output)?;
 
-        OP_ACCOUNT_CURRENT
+        self.account.current.reset();
+        self.account
+            .current
             .with_label_values(&[
                 &account.id,
                 &account.name,
@@ -44,6 +54,9 @@ pub(crate) fn read_account(&self) {
                 &account.created_at,
             ])
             .set(1);
+        self.snapshot.write().unwrap().account = Some(account);
+
+        Ok(())
     }
 }
 
@@ -56,13 +69,16 @@ mod tests {
     use crate::testing::metrics_collector;
 
     #[rstest]
-    fn test_read_account(metrics_collector: OpMetricsCollector) -> Result<()> {
+    #[tokio::test]
+    async fn test_read_account(metrics_collector: OpMetricsCollector) -> Result<()> {
         // Act
-        metrics_collector.read_account();
+        metrics_collector.read_account().await?;
 
         // Assert
         assert_eq!(
-            OP_ACCOUNT_CURRENT
+            metrics_collector
+                .account
+                .current
                 .get_metric_with_label_values(&[
                     "??????????????????????????",
                     "**********",
@@ -1,21 +1,33 @@
-use lazy_static::lazy_static;
-use prometheus::{register_int_gauge_vec, IntGaugeVec};
+use prometheus::{register_int_gauge_vec_with_registry, IntGaugeVec, Registry};
 
 use super::OpMetricsCollector;
 
-lazy_static! {
-    static ref OP_EXPORTER_BUILDINFO: IntGaugeVec = register_int_gauge_vec!(
-        "op_exporter_buildinfo",
-        "Build information of this exporter.",
-        &["version"]
-    )
-    .unwrap();
+/// Version of this exporter.
+pub(crate) const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Build information metrics of this exporter.
+pub(super) struct BuildInfoMetrics {
+    buildinfo: IntGaugeVec,
+}
+
+impl BuildInfoMetrics {
+    pub(super) fn new(registry: &Registry) -> prometheus::Result<Self> {
+        Ok(BuildInfoMetrics {
+            buildinfo: register_int_gauge_vec_with_registry!(
+                "op_exporter_buildinfo",
+                "Build information of this exporter.",
+                &["version"],
+                registry
+            )?,
+        })
+    }
 }
 
 impl OpMetricsCollector {
     pub(crate) fn read_buildinfo(&self) {
-        OP_EXPORTER_BUILDINFO
-            .with_label_values(&[env!("CARGO_PKG_VERSION")])
+        self.build_info
+            .buildinfo
+            .with_label_values(&[VERSION])
             .set(1);
     }
 }
@@ -35,7 +47,9 @@ fn test_read_buildinfo(metrics_collector: OpMetricsCollector) -> Result<()> {
 
         // Assert
         assert_eq!(
-            OP_EXPORTER_BUILDINFO
+            metrics_collector
+                .build_info
+                .buildinfo
                 .get_metric_with_label_values(&["0.4.2"])?
                 .get(),
             1
@@ -1,41 +1,57 @@
 use std::collections::HashMap;
 
-use lazy_static::lazy_static;
 #[cfg(test)]
 use mockall::predicate::*;
-use prometheus::{register_int_gauge_vec, IntGaugeVec};
+use prometheus::{register_int_gauge_vec_with_registry, IntGaugeVec, Registry};
 use serde::Deserialize;
 
-use super::OpMetricsCollector;
-
-lazy_static! {
-    static ref OP_DOCUMENT_COUNT_TOTAL: IntGaugeVec =
-        register_int_gauge_vec!("op_document_count_total", "Total number of documents.", &[])
-            .unwrap();
-    static ref OP_DOCUMENT_COUNT_PER_VAULT: IntGaugeVec = register_int_gauge_vec!(
-        "op_document_count_per_vault",
-        "Number of documents per vault.",
-        &["vault"]
-    )
-    .unwrap();
-    static ref OP_DOCUMENT_COUNT_PER_TAG: IntGaugeVec = register_int_gauge_vec!(
-        "op_document_count_per_tag",
-        "Number of documents per tag.",
-        &["tag"]
-    )
-    .unwrap();
-    static ref OP_DOCUMENT_FILE_SIZE_PER_VAULT: IntGaugeVec = register_int_gauge_vec!(
-        "op_document_file_size_per_vault_bytes",
-        "Size of file in documents per vault, in bytes.",
-        &["vault"],
-    )
-    .unwrap();
-    static ref OP_DOCUMENT_FILE_SIZE_PER_TAG: IntGaugeVec = register_int_gauge_vec!(
-        "op_document_file_size_per_tag_bytes",
-        "Size of file in documents per tag, in bytes.",
-        &["tag"],
-    )
-    .unwrap();
+use super::{snapshot::{active_counts, DocumentSummary},
+            state_label, CollectError, OpMetricsCollector, ACTIVE, ARCHIVED};
+
+/// Metrics of documents.
+pub(super) struct DocumentMetrics {
+    count_total: IntGaugeVec,
+    count_per_vault: IntGaugeVec,
+    count_per_tag: IntGaugeVec,
+    file_size_per_vault: IntGaugeVec,
+    file_size_per_tag: IntGaugeVec,
+}
+
+impl DocumentMetrics {
+    pub(super) fn new(registry: &Registry) -> prometheus::Result<Self> {
+        Ok(DocumentMetrics {
+            count_total: register_int_gauge_vec_with_registry!(
+                "op_document_count_total",
+                "Total number of documents.",
+                &["state"],
+                registry
+            )?,
+            count_per_vault: register_int_gauge_vec_with_registry!(
+                "op_document_count_per_vault",
+                "Number of documents per vault.",
+                &["vault", "state"],
+                registry
+            )?,
+            count_per_tag: register_int_gauge_vec_with_registry!(
+                "op_document_count_per_tag",
+                "Number of documents per tag.",
+                &["tag", "state"],
+                registry
+            )?,
+            file_size_per_vault: register_int_gauge_vec_with_registry!(
+                "op_document_file_size_per_vault_bytes",
+                "Size of file in documents per vault, in bytes.",
+                &["vault", "state"],
+                registry
+            )?,
+            file_size_per_tag: register_int_gauge_vec_with_registry!(
+                "op_document_file_size_per_tag_bytes",
+                "Size of file in documents per tag, in bytes.",
+                &["tag", "state"],
+                registry
+            )?,
+        })
+    }
 }
 
 #[derive(Deserialize, Debug)]
@@ -56,6 +72,7 @@ struct Document {
     pub(crate) created_at: String,
     #[allow(dead_code)]
     pub(crate) updated_at: String,
+    pub(crate) state: Option<String>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -66,7 +83,7 @@ struct DocumentVault {
 }
 
 impl OpMetricsCollector {
-    pub(crate) fn read_document(&self) {
+    pub(crate) async fn read_document(&self) -> Result<(), CollectError> {
         let output = self
             .command_executor
             .exec(vec![
@@ -76,16 +93,18 @@ pub(crate) fn read_document(&self) {
                 "json",
                 "--include-archive",
             ])
-            .unwrap();
-        let documents: Vec<Document> = serde_json::from_str(&output).unwrap();
+            .await?;
+        let documents: Vec<Document> = serde_json::from_str(&output)?;
 
         // Gather metrics
+        let mut count_total = HashMap::from([(ACTIVE, 0), (ARCHIVED, 0)]);
         let mut count_per_vault = HashMap::new();
         let mut count_per_tag = HashMap::new();
         let mut file_size_per_vault = HashMap::new();
         let mut file_size_per_tag = HashMap::new();
 
         for document in &documents {
+            let state = state_label(document.state.as_deref());
             let vault_id = document.vault.id.clone();
             let file_size = match &document.overview_ainfo {
                 Some(overview_ainfo) => parse_file_size_bytes(overview_ainfo).unwrap_or_default(),
@@ -93,40 +112,67 @@ pub(crate) fn read_document(&self) {
             };
             let tags = document.tags.clone().unwrap_or_default();
 
-            *count_per_vault.entry(vault_id.clone()).or_insert(0) += 1;
-            *file_size_per_vault.entry(vault_id).or_insert(0) += file_size;
+            *count_total.entry(state).or_insert(0) += 1;
+            *count_per_vault
+                .entry((vault_id.clone(), state))
+                .or_insert(0) += 1;
+            *file_size_per_vault.entry((vault_id, state)).or_insert(0) += file_size;
 
             for tag in tags {
-                *count_per_tag.entry(tag.clone()).or_insert(0) += 1;
-                *file_size_per_tag.entry(tag).or_insert(0) += file_size;
+                *count_per_tag.entry((tag.clone(), state)).or_insert(0) += 1;
+                *file_size_per_tag.entry((tag, state)).or_insert(0) += file_size;
             }
         }
 
-        // Set metrics
-        OP_DOCUMENT_COUNT_TOTAL
-            .with_label_values::<&str>(&[])
-            .set(documents.len() as i64);
+        // Set metrics, replacing label sets of the previous collection
+        count_total.iter().for_each(|(state, count)| {
+            self.document
+                .count_total
+                .with_label_values(&[state])
+                .set(*count);
+        });
+
+        self.document.count_per_vault.reset();
+        self.document.count_per_tag.reset();
+        self.document.file_size_per_vault.reset();
+        self.document.file_size_per_tag.reset();
 
-        count_per_vault.iter().for_each(|(vault, count)| {
-            OP_DOCUMENT_COUNT_PER_VAULT
-                .with_label_values(&[vault])
+        count_per_vault.iter().for_each(|((vault, state), count)| {
+            self.document
+                .count_per_vault
+                .with_label_values(&[vault.as_str(), state])
                 .set(*count);
         });
-        count_per_tag.iter().for_each(|(tag, count)| {
-            OP_DOCUMENT_COUNT_PER_TAG
-                .with_label_values(&[tag])
+        count_per_tag.iter().for_each(|((tag, state), count)| {
+            self.document
+                .count_per_tag
+                .with_label_values(&[tag.as_str(), state])
                 .set(*count);
         });
-        file_size_per_vault.iter().for_each(|(vault, size)| {
-            OP_DOCUMENT_FILE_SIZE_PER_VAULT
-                .with_label_values(&[vault])
+        file_size_per_vault
+            .iter()
+            .for_each(|((vault, state), size)| {
+                self.document
+                    .file_size_per_vault
+                    .with_label_values(&[vault.as_str(), state])
+                    .set(*size);
+            });
+        file_size_per_tag.iter().for_each(|((tag, state), size)| {
+            self.document
+                .file_size_per_tag
+                .with_label_values(&[tag.as_str(), state])
                 .set(*size);
         });
-        file_size_per_tag.iter().for_each(|(tag, size)| {
-            OP_DOCUMENT_FILE_SIZE_PER_TAG
-                .with_label_values(&[tag])
-                .set(*size);
+        self.snapshot.write().unwrap().documents = Some(DocumentSummary {
+            active: count_total[ACTIVE] as usize,
+            archived: count_total[ARCHIVED] as usize,
+            per_vault: active_counts(&count_per_vault),
+            per_tag: active_counts(&count_per_tag),
+            file_size_per_vault_bytes: active_counts(&file_size_per_vault),
+            file_size_per_tag_bytes: active_counts(&file_size_per_tag),
         });
+
+        Ok(())
     }
 }
 
@@ -175,38 +221,49 @@ mod tests {
     use crate::testing::metrics_collector;
 
     #[rstest]
-    fn test_read_document(metrics_collector: OpMetricsCollector) -> Result<()> {
+    #[tokio::test]
+    async fn test_read_document(metrics_collector: OpMetricsCollector) -> Result<()> {
         // Act
-        metrics_collector.read_document();
+        metrics_collector.read_document().await?;
 
         // Assert
         assert_eq!(
-            OP_DOCUMENT_COUNT_TOTAL
-                .get_metric_with_label_values::<&str>(&[])?
+            metrics_collector
+                .document
+                .count_total
+                .get_metric_with_label_values(&["active"])?
                 .get(),
             4
         );
         assert_eq!(
-            OP_DOCUMENT_COUNT_PER_VAULT
-                .get_metric_with_label_values(&["36vhq4xz3r6hnemzadk33evi4a"])?
+            metrics_collector
+                .document
+                .count_per_vault
+                .get_metric_with_label_values(&["36vhq4xz3r6hnemzadk33evi4a", "active"])?
                 .get(),
             4
         );
         assert_eq!(
-            OP_DOCUMENT_COUNT_PER_TAG
-                .get_metric_with_label_values(&["test"])?
+            metrics_collector
+                .document
+                .count_per_tag
+                .get_metric_with_label_values(&["test", "active"])?
                 .get(),
             4
         );
         assert_eq!(
-            OP_DOCUMENT_FILE_SIZE_PER_VAULT
-                .get_metric_with_label_values(&["36vhq4xz3r6hnemzadk33evi4a"])?
+            metrics_collector
+                .document
+                .file_size_per_vault
+                .get_metric_with_label_values(&["36vhq4xz3r6hnemzadk33evi4a", "active"])?
                 .get(),
             10494986
         );
         assert_eq!(
-            OP_DOCUMENT_FILE_SIZE_PER_TAG
-                .get_metric_with_label_values(&["test"])?
+            metrics_collector
+                .document
+                .file_size_per_tag
+                .get_metric_with_label_values(&["test", "active"])?
                 .get(),
             10494986
         );
@@ -0,0 +1,203 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use prometheus::{core::Collector, register_gauge_vec_with_registry,
+                 register_int_counter_vec_with_registry, register_int_gauge_vec_with_registry,
+                 GaugeVec, IntCounterVec, IntGaugeVec, Registry};
+
+use super::{CollectError, Metrics, OpMetricsCollector};
+
+/// Metrics of the exporter's own collections.
+pub(super) struct ExporterMetrics {
+    last_collection_timestamp: IntGaugeVec,
+    collector_success: IntGaugeVec,
+    collector_duration: GaugeVec,
+    collector_errors: IntCounterVec,
+}
+
+impl ExporterMetrics {
+    pub(super) fn new(registry: &Registry) -> prometheus::Result<Self> {
+        Ok(ExporterMetrics {
+            last_collection_timestamp: register_int_gauge_vec_with_registry!(
+                "op_exporter_last_collection_timestamp_seconds",
+                "Unix timestamp of the last metrics collection, in seconds.",
+                &[],
+                registry
+            )?,
+            collector_success: register_int_gauge_vec_with_registry!(
+                "op_exporter_collector_success",
+                "Whether the last collection of the collector succeeded.",
+                &["collector"],
+                registry
+            )?,
+            collector_duration: register_gauge_vec_with_registry!(
+                "op_exporter_collector_duration_seconds",
+                "Duration of the last collection of the collector, in seconds.",
+                &["collector"],
+                registry
+            )?,
+            collector_errors: register_int_counter_vec_with_registry!(
+                "op_exporter_collector_errors_total",
+                "Total number of collection errors.",
+                &["collector", "kind"],
+                registry
+            )?,
+        })
+    }
+}
+
+impl OpMetricsCollector {
+    pub(crate) fn mark_collection_done(&self) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+
+        self.exporter
+            .last_collection_timestamp
+            .with_label_values::<&str>(&[])
+            .set(now.as_secs() as i64);
+    }
+
+    /// Continue counters of a collector being replaced on reload, so they do not look reset to Prometheus.
+    pub(crate) fn inherit_counters(&self, previous: &OpMetricsCollector) {
+        for mf in previous.exporter.collector_errors.collect() {
+            for m in mf.get_metric() {
+                let label = |name: &str| {
+                    m.get_label()
+                        .iter()
+                        .find(|l| l.name() == name)
+                        .map_or("", |l| l.value())
+                };
+                self.exporter
+                    .collector_errors
+                    .with_label_values(&[label("collector"), label("kind")])
+                    .inc_by(m.get_counter().value() as u64);
+            }
+        }
+    }
+
+    pub(crate) fn record_collection(
+        &self,
+        metric: Metrics,
+        duration: Duration,
+        result: &Result<(), CollectError>,
+    ) {
+        let collector = metric.to_string();
+        self.exporter
+            .collector_success
+            .with_label_values(&[&collector])
+            .set(result.is_ok() as i64);
+        self.exporter
+            .collector_duration
+            .with_label_values(&[&collector])
+            .set(duration.as_secs_f64());
+        if let Err(err) = result {
+            self.exporter
+                .collector_errors
+                .with_label_values(&[&collector, err.kind()])
+                .inc();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use rstest::*;
+
+    use super::*;
+    use crate::testing::metrics_collector;
+
+    #[rstest]
+    fn test_mark_collection_done(metrics_collector: OpMetricsCollector) -> Result<()> {
+        // Arrange
+        let before = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+
+        // Act
+        metrics_collector.mark_collection_done();
+
+        // Assert
+        assert!(
+            metrics_collector
+                .exporter
+                .last_collection_timestamp
+                .get_metric_with_label_values::<&str>(&[])?
+                .get()
+                >= before
+        );
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_record_collection(metrics_collector: OpMetricsCollector) -> Result<()> {
+        // Arrange
+        let error = serde_json::from_str::<()>("").unwrap_err();
+
+        // Act
+        metrics_collector.record_collection(Metrics::Group, Duration::from_millis(1500), &Ok(()));
+        metrics_collector.record_collection(
+            Metrics::Vault,
+            Duration::from_millis(500),
+            &Err(CollectError::Parse(error)),
+        );
+
+        // Assert
+        let exporter = &metrics_collector.exporter;
+        assert_eq!(
+            exporter
+                .collector_success
+                .get_metric_with_label_values(&["group"])?
+                .get(),
+            1
+        );
+        assert_eq!(
+            exporter
+                .collector_duration
+                .get_metric_with_label_values(&["group"])?
+                .get(),
+            1.5
+        );
+        assert_eq!(
+            exporter
+                .collector_success
+                .get_metric_with_label_values(&["vault"])?
+                .get(),
+            0
+        );
+        assert_eq!(
+            exporter
+                .collector_errors
+                .get_metric_with_label_values(&["vault", "parse"])?
+                .get(),
+            1
+        );
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_inherit_counters(metrics_collector: OpMetricsCollector) -> Result<()> {
+        // Arrange
+        let error = || CollectError::Parse(serde_json::from_str::<()>("").unwrap_err());
+        for _ in 0..2 {
+            metrics_collector.record_collection(Metrics::Vault, Duration::ZERO, &Err(error()));
+        }
+        let reloaded = OpMetricsCollector::new(Box::new(crate::testing::command_executor()));
+
+        // Act
+        reloaded.inherit_counters(&metrics_collector);
+        reloaded.record_collection(Metrics::Vault, Duration::ZERO, &Err(error()));
+
+        // Assert
+        assert_eq!(
+            reloaded
+                .exporter
+                .collector_errors
+                .get_metric_with_label_values(&["vault", "parse"])?
+                .get(),
+            3
+        );
+
+        Ok(())
+    }
+}
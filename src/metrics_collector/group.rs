@@ -1,14 +1,26 @@
-use lazy_static::lazy_static;
 #[cfg(test)]
 use mockall::predicate::*;
-use prometheus::{register_int_gauge_vec, IntGaugeVec};
+use prometheus::{register_int_gauge_vec_with_registry, IntGaugeVec, Registry};
 use serde::Deserialize;
 
-use super::OpMetricsCollector;
+use super::{CollectError, OpMetricsCollector};
 
-lazy_static! {
-    static ref OP_GROUP_COUNT_TOTAL: IntGaugeVec =
-        register_int_gauge_vec!("op_group_count_total", "Total number of groups.", &[]).unwrap();
+/// Metrics of groups.
+pub(super) struct GroupMetrics {
+    count_total: IntGaugeVec,
+}
+
+impl GroupMetrics {
+    pub(super) fn new(registry: &Registry) -> prometheus::Result<Self> {
+        Ok(GroupMetrics {
+            count_total: register_int_gauge_vec_with_registry!(
+                "op_group_count_total",
+                "Total number of groups.",
+                &[],
+                registry
+            )?,
+        })
+    }
 }
 
 #[derive(Deserialize, Debug)]
@@ -26,16 +38,20 @@ struct Group {
 }
 
 impl OpMetricsCollector {
-    pub(crate) fn read_group(&self) {
+    pub(crate) async fn read_group(&self) -> Result<(), CollectError> {
         let output = self
             .command_executor
             .exec(vec!["group", "list", "--format", "json"])
-            .unwrap();
-        let groups: Vec<Group> = serde_json::from_str(&output).unwrap();
+            .await?;
+        let groups: Vec<Group> = serde_json::from_str(&output)?;
 
-        OP_GROUP_COUNT_TOTAL
+        self.group
+            .count_total
             .with_label_values::<&str>(&[])
             .set(groups.len() as i64);
+        self.snapshot.write().unwrap().group_count = Some(groups.len());
+
+        Ok(())
     }
 }
 
@@ -48,12 +64,15 @@ mod tests {
     use crate::testing::metrics_collector;
 
     #[rstest]
-    fn test_read_group(metrics_collector: OpMetricsCollector) -> Result<()> {
-        metrics_collector.read_group();
+    #[tokio::test]
+    async fn test_read_group(metrics_collector: OpMetricsCollector) -> Result<()> {
+        metrics_collector.read_group().await?;
 
         // Assert
         assert_eq!(
-            OP_GROUP_COUNT_TOTAL
+            metrics_collector
+                .group
+                .count_total
                 .get_metric_with_label_values::<&str>(&[])?
                 .get(),
             4
@@ -1,34 +1,136 @@
-use std::collections::HashMap;
+use std::{collections::HashMap,
+          str::FromStr,
+          time::{Duration, SystemTime}};
 
-use lazy_static::lazy_static;
 #[cfg(test)]
 use mockall::predicate::*;
-use prometheus::{register_int_gauge_vec, IntGaugeVec};
+use prometheus::{register_int_gauge_vec_with_registry, IntGaugeVec, Registry};
 use serde::Deserialize;
 
-use super::OpMetricsCollector;
-
-lazy_static! {
-    static ref OP_ITEM_COUNT_TOTAL: IntGaugeVec =
-        register_int_gauge_vec!("op_item_count_total", "Total number of items.", &[]).unwrap();
-    static ref OP_ITEM_COUNT_PER_VAULT: IntGaugeVec = register_int_gauge_vec!(
-        "op_item_count_per_vault",
-        "Number of items per vault.",
-        &["vault"]
-    )
-    .unwrap();
-    static ref OP_ITEM_COUNT_PER_TAG: IntGaugeVec = register_int_gauge_vec!(
-        "op_item_count_per_tag",
-        "Number of items per tag.",
-        &["tag"]
-    )
-    .unwrap();
-    static ref OP_ITEM_COUNT_PER_CATEGORY: IntGaugeVec = register_int_gauge_vec!(
-        "op_item_count_per_category",
-        "Number of items per category.",
-        &["category"]
-    )
-    .unwrap();
+use super::{snapshot::{active_counts, ItemSummary},
+            state_label, CollectError, OpMetricsCollector, ACTIVE, ARCHIVED};
+
+/// Metrics of items.
+pub(super) struct ItemMetrics {
+    count_total: IntGaugeVec,
+    count_per_vault: IntGaugeVec,
+    count_per_tag: IntGaugeVec,
+    count_per_category: IntGaugeVec,
+    count_per_editor: IntGaugeVec,
+    age: IntGaugeVec,
+    last_update_age: IntGaugeVec,
+    stale_count: IntGaugeVec,
+    stale_count_per_tag: IntGaugeVec,
+}
+
+const DAY: f64 = 24.0 * 60.0 * 60.0;
+
+/// Upper bounds of item age buckets, from a day to two years and the rest.
+const AGE_BUCKETS: &[f64] = &[
+    DAY,
+    7.0 * DAY,
+    30.0 * DAY,
+    90.0 * DAY,
+    180.0 * DAY,
+    365.0 * DAY,
+    730.0 * DAY,
+    f64::INFINITY,
+];
+
+impl ItemMetrics {
+    pub(super) fn new(registry: &Registry) -> prometheus::Result<Self> {
+        Ok(ItemMetrics {
+            count_total: register_int_gauge_vec_with_registry!(
+                "op_item_count_total",
+                "Total number of items.",
+                &["state"],
+                registry
+            )?,
+            count_per_vault: register_int_gauge_vec_with_registry!(
+                "op_item_count_per_vault",
+                "Number of items per vault.",
+                &["vault", "state"],
+                registry
+            )?,
+            count_per_tag: register_int_gauge_vec_with_registry!(
+                "op_item_count_per_tag",
+                "Number of items per tag.",
+                &["tag", "state"],
+                registry
+            )?,
+            count_per_category: register_int_gauge_vec_with_registry!(
+                "op_item_count_per_category",
+                "Number of items per category.",
+                &["category", "state"],
+                registry
+            )?,
+            count_per_editor: register_int_gauge_vec_with_registry!(
+                "op_item_count_per_editor",
+                "Number of items per vault and user who last edited them.",
+                &["vault", "editor", "editor_name", "editor_email", "state"],
+                registry
+            )?,
+            // Recounted on each collection, so exposed as gauges rather than a histogram of ever-growing counters
+            age: register_int_gauge_vec_with_registry!(
+                "op_item_age_seconds_bucket",
+                "Number of items created at most `le` seconds ago. A gauge, use `histogram_quantile()` without `rate()`.",
+                &["vault", "category", "state", "le"],
+                registry
+            )?,
+            last_update_age: register_int_gauge_vec_with_registry!(
+                "op_item_last_update_age_seconds_bucket",
+                "Number of items last updated at most `le` seconds ago. A gauge, use `histogram_quantile()` without `rate()`.",
+                &["vault", "category", "state", "le"],
+                registry
+            )?,
+            stale_count: register_int_gauge_vec_with_registry!(
+                "op_item_stale_count",
+                "Number of items not updated within the threshold.",
+                &["vault", "category", "state", "threshold"],
+                registry
+            )?,
+            stale_count_per_tag: register_int_gauge_vec_with_registry!(
+                "op_item_stale_count_per_tag",
+                "Number of items per tag not updated within the threshold.",
+                &["tag", "state", "threshold"],
+                registry
+            )?,
+        })
+    }
+}
+
+/// Time since last update after which an item is stale, such as `90d` or `1y`.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct StaleThreshold {
+    duration: Duration,
+    /// Threshold as configured, used as the `threshold` label.
+    label: String,
+}
+
+impl FromStr for StaleThreshold {
+    type Err = humantime::DurationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        Ok(StaleThreshold {
+            duration: humantime::parse_duration(s)?,
+            label: s.to_string(),
+        })
+    }
+}
+
+/// Time since last update after which items are stale, optionally set per category.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct StaleThresholds {
+    pub(crate) default: Vec<StaleThreshold>,
+    /// Thresholds replacing the default ones for a category, e.g. `API_CREDENTIAL`.
+    pub(crate) per_category: HashMap<String, Vec<StaleThreshold>>,
+}
+
+impl StaleThresholds {
+    fn for_category(&self, category: &str) -> &[StaleThreshold] {
+        self.per_category.get(category).unwrap_or(&self.default)
+    }
 }
 
 #[derive(Deserialize, Debug)]
@@ -42,14 +144,12 @@ struct Item {
     pub(crate) version: i32,
     pub(crate) vault: ItemVault,
     pub(crate) category: String,
-    #[allow(dead_code)]
     pub(crate) last_edited_by: String,
-    #[allow(dead_code)]
     pub(crate) created_at: String,
-    #[allow(dead_code)]
     pub(crate) updated_at: String,
     #[allow(dead_code)]
     pub(crate) additional_information: Option<String>,
+    pub(crate) state: Option<String>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -60,7 +160,19 @@ struct ItemVault {
 }
 
 impl OpMetricsCollector {
-    pub(crate) fn read_item(&self) {
+    /// Set thresholds of the stale item metrics. No item is counted as stale if not set.
+    pub(crate) fn with_stale_thresholds(mut self, stale_thresholds: StaleThresholds) -> Self {
+        self.stale_thresholds = stale_thresholds;
+        self
+    }
+
+    /// Label editors of items with their user IDs only, without listing users to resolve names and emails.
+    pub(crate) fn with_editor_ids_only(mut self, editor_ids_only: bool) -> Self {
+        self.editor_ids_only = editor_ids_only;
+        self
+    }
+
+    pub(crate) async fn read_item(&self) -> Result<(), CollectError> {
         let output = self
             .command_executor
             .exec(vec![
@@ -70,109 +182,562 @@ pub(crate) fn read_item(&self) {
                 "json",
                 "--include-archive",
             ])
-            .unwrap();
-        let items: Vec<Item> = serde_json::from_str(&output).unwrap();
+            .await?;
+        let items: Vec<Item> = serde_json::from_str(&output)?;
+
+        // Editors not found, such as removed users or service accounts, are left with IDs only
+        let users = if self.editor_ids_only {
+            HashMap::new()
+        } else {
+            self.users_by_id().await.unwrap_or_else(|err| {
+                log::warn!("Failed to list users to resolve item editors: {}", err);
+                HashMap::new()
+            })
+        };
 
         // Gather metrics
+        let mut count_total = HashMap::from([(ACTIVE, 0), (ARCHIVED, 0)]);
         let mut count_per_vault = HashMap::new();
         let mut count_per_tag = HashMap::new();
         let mut count_per_category = HashMap::new();
+        let mut count_per_editor = HashMap::new();
         for item in &items {
+            let state = state_label(item.state.as_deref());
+            *count_total.entry(state).or_insert(0) += 1;
+
             let vault_id = item.vault.id.clone();
-            *count_per_vault.entry(vault_id).or_insert(0) += 1;
+            *count_per_vault.entry((vault_id, state)).or_insert(0) += 1;
 
             let tags = item.tags.clone().unwrap_or_default();
             for tag in tags {
-                *count_per_tag.entry(tag).or_insert(0) += 1;
+                *count_per_tag.entry((tag, state)).or_insert(0) += 1;
             }
 
             let category = item.category.clone();
-            *count_per_category.entry(category).or_insert(0) += 1;
-        }
+            *count_per_category.entry((category, state)).or_insert(0) += 1;
 
-        // Set metrics
-        OP_ITEM_COUNT_TOTAL
-            .with_label_values::<&str>(&[])
-            .set(items.len() as i64);
+            let editor = (item.vault.id.clone(), item.last_edited_by.clone(), state);
+            *count_per_editor.entry(editor).or_insert(0) += 1;
+        }
 
-        count_per_vault.iter().for_each(|(vault, count)| {
-            OP_ITEM_COUNT_PER_VAULT
-                .with_label_values(&[vault])
+        // Set metrics, replacing label sets of the previous collection
+        count_total.iter().for_each(|(state, count)| {
+            self.item
+                .count_total
+                .with_label_values(&[state])
                 .set(*count);
         });
-        count_per_tag.iter().for_each(|(tag, count)| {
-            OP_ITEM_COUNT_PER_TAG.with_label_values(&[tag]).set(*count);
+
+        self.item.count_per_vault.reset();
+        self.item.count_per_tag.reset();
+        self.item.count_per_category.reset();
+        self.item.count_per_editor.reset();
+        self.item.age.reset();
+        self.item.last_update_age.reset();
+        self.item.stale_count.reset();
+        self.item.stale_count_per_tag.reset();
+        count_per_vault.iter().for_each(|((vault, state), count)| {
+            self.item
+                .count_per_vault
+                .with_label_values(&[vault.as_str(), state])
+                .set(*count);
         });
-        count_per_category.iter().for_each(|(category, count)| {
-            OP_ITEM_COUNT_PER_CATEGORY
-                .with_label_values(&[category])
+        count_per_tag.iter().for_each(|((tag, state), count)| {
+            self.item
+                .count_per_tag
+                .with_label_values(&[tag.as_str(), state])
                 .set(*count);
         });
+        count_per_category
+            .iter()
+            .for_each(|((category, state), count)| {
+                self.item
+                    .count_per_category
+                    .with_label_values(&[category.as_str(), state])
+                    .set(*count);
+            });
+        count_per_editor
+            .iter()
+            .for_each(|((vault, editor, state), count)| {
+                let user = users.get(editor);
+                self.item
+                    .count_per_editor
+                    .with_label_values(&[
+                        vault.as_str(),
+                        editor.as_str(),
+                        user.map_or("", |u| u.name.as_str()),
+                        user.map_or("", |u| u.email.as_str()),
+                        state,
+                    ])
+                    .set(*count);
+            });
+        let now = SystemTime::now();
+        let mut age = HashMap::new();
+        let mut last_update_age = HashMap::new();
+        let mut stale_count = HashMap::new();
+        let mut stale_count_per_tag = HashMap::new();
+        for item in &items {
+            let state = state_label(item.state.as_deref());
+            let key = (item.vault.id.clone(), item.category.clone(), state);
+            if let Some(created) = age_seconds(&item.created_at, now) {
+                observe_age(age.entry(key.clone()).or_default(), created);
+            }
+            let Some(age) = age_seconds(&item.updated_at, now) else {
+                continue;
+            };
+            observe_age(last_update_age.entry(key).or_default(), age);
+
+            // Count fresh items as zero too, so alerts resolve once stale items are updated
+            for threshold in self.stale_thresholds.for_category(&item.category) {
+                let stale = (age > threshold.duration.as_secs_f64()) as i64;
+                let key = (
+                    item.vault.id.clone(),
+                    item.category.clone(),
+                    state,
+                    threshold.label.clone(),
+                );
+                *stale_count.entry(key).or_insert(0) += stale;
+                for tag in item.tags.iter().flatten() {
+                    *stale_count_per_tag
+                        .entry((tag.clone(), state, threshold.label.clone()))
+                        .or_insert(0) += stale;
+                }
+            }
+        }
+        for (gauge, buckets) in [
+            (&self.item.age, age),
+            (&self.item.last_update_age, last_update_age),
+        ] {
+            for ((vault, category, state), counts) in buckets {
+                for (bound, count) in AGE_BUCKETS.iter().zip(counts) {
+                    let le = if bound.is_infinite() {
+                        "+Inf".to_string()
+                    } else {
+                        // Float form as OpenMetrics requires, e.g. `86400.0`
+                        format!("{bound:?}")
+                    };
+                    gauge
+                        .with_label_values(&[vault.as_str(), category.as_str(), state, &le])
+                        .set(count);
+                }
+            }
+        }
+        stale_count
+            .iter()
+            .for_each(|((vault, category, state, threshold), count)| {
+                self.item
+                    .stale_count
+                    .with_label_values(&[
+                        vault.as_str(),
+                        category.as_str(),
+                        state,
+                        threshold.as_str(),
+                    ])
+                    .set(*count);
+            });
+        stale_count_per_tag
+            .iter()
+            .for_each(|((tag, state, threshold), count)| {
+                self.item
+                    .stale_count_per_tag
+                    .with_label_values(&[tag.as_str(), state, threshold.as_str()])
+                    .set(*count);
+            });
+        self.snapshot.write().unwrap().items = Some(ItemSummary {
+            active: count_total[ACTIVE] as usize,
+            archived: count_total[ARCHIVED] as usize,
+            per_vault: active_counts(&count_per_vault),
+            per_category: active_counts(&count_per_category),
+            per_tag: active_counts(&count_per_tag),
+        });
+
+        Ok(())
+    }
+}
+
+/// Count an age into cumulative counts of `AGE_BUCKETS`, allocating them on first use.
+fn observe_age(counts: &mut Vec<i64>, age: f64) {
+    counts.resize(AGE_BUCKETS.len(), 0);
+    for (bound, count) in AGE_BUCKETS.iter().zip(counts.iter_mut()) {
+        *count += (age <= *bound) as i64;
+    }
+}
+
+/// Seconds elapsed from RFC 3339 timestamp (e.g. `"2024-09-03T12:54:11Z"`) to `now`.
+fn age_seconds(timestamp: &str, now: SystemTime) -> Option<f64> {
+    match humantime::parse_rfc3339_weak(timestamp) {
+        Ok(time) => Some(now.duration_since(time).unwrap_or_default().as_secs_f64()),
+        Err(e) => {
+            log::error!("Failed to parse timestamp {}: {}", timestamp, e);
+            None
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
     use anyhow::Result;
+    use prometheus::core::Collector;
     use rstest::*;
 
     use super::*;
-    use crate::testing::metrics_collector;
+    use crate::{command_executor::MockCommandExecutor, metrics_collector::USERS_TTL, test_dir,
+                testing::metrics_collector};
 
     #[rstest]
-    fn test_read_item(metrics_collector: OpMetricsCollector) -> Result<()> {
-        metrics_collector.read_item();
+    #[tokio::test]
+    async fn test_read_item(metrics_collector: OpMetricsCollector) -> Result<()> {
+        metrics_collector.read_item().await?;
 
         // Assert
         assert_eq!(
-            OP_ITEM_COUNT_TOTAL
-                .get_metric_with_label_values::<&str>(&[])?
+            metrics_collector
+                .item
+                .count_total
+                .get_metric_with_label_values(&["active"])?
                 .get(),
-            5
+            4
         );
         assert_eq!(
-            OP_ITEM_COUNT_PER_VAULT
-                .get_metric_with_label_values(&["36vhq4xz3r6hnemzadk33evi4a"])?
+            metrics_collector
+                .item
+                .count_total
+                .get_metric_with_label_values(&["archived"])?
                 .get(),
-            5
+            1
         );
         assert_eq!(
-            OP_ITEM_COUNT_PER_TAG
-                .get_metric_with_label_values(&["dev"])?
+            metrics_collector
+                .item
+                .count_per_vault
+                .get_metric_with_label_values(&["36vhq4xz3r6hnemzadk33evi4a", "active"])?
+                .get(),
+            4
+        );
+        assert_eq!(
+            metrics_collector
+                .item
+                .count_per_vault
+                .get_metric_with_label_values(&["36vhq4xz3r6hnemzadk33evi4a", "archived"])?
                 .get(),
             1
         );
         assert_eq!(
-            OP_ITEM_COUNT_PER_TAG
-                .get_metric_with_label_values(&["test"])?
+            metrics_collector
+                .item
+                .count_per_tag
+                .get_metric_with_label_values(&["dev", "active"])?
                 .get(),
-            4
+            1
+        );
+        assert_eq!(
+            metrics_collector
+                .item
+                .count_per_tag
+                .get_metric_with_label_values(&["test", "active"])?
+                .get(),
+            3
         );
         assert_eq!(
-            OP_ITEM_COUNT_PER_CATEGORY
-                .get_metric_with_label_values(&["DOCUMENT"])?
+            metrics_collector
+                .item
+                .count_per_tag
+                .get_metric_with_label_values(&["test", "archived"])?
                 .get(),
             1
         );
         assert_eq!(
-            OP_ITEM_COUNT_PER_CATEGORY
-                .get_metric_with_label_values(&["LOGIN"])?
+            metrics_collector
+                .item
+                .count_per_category
+                .get_metric_with_label_values(&["DOCUMENT", "active"])?
                 .get(),
-            2
+            1
+        );
+        assert_eq!(
+            metrics_collector
+                .item
+                .count_per_category
+                .get_metric_with_label_values(&["LOGIN", "active"])?
+                .get(),
+            1
+        );
+        assert_eq!(
+            metrics_collector
+                .item
+                .count_per_category
+                .get_metric_with_label_values(&["LOGIN", "archived"])?
+                .get(),
+            1
         );
         assert_eq!(
-            OP_ITEM_COUNT_PER_CATEGORY
-                .get_metric_with_label_values(&["SECURE_NOTE"])?
+            metrics_collector
+                .item
+                .count_per_category
+                .get_metric_with_label_values(&["SECURE_NOTE", "active"])?
                 .get(),
             1
         );
         assert_eq!(
-            OP_ITEM_COUNT_PER_CATEGORY
-                .get_metric_with_label_values(&["SSH_KEY"])?
+            metrics_collector
+                .item
+                .count_per_category
+                .get_metric_with_label_values(&["SSH_KEY", "active"])?
                 .get(),
             1
         );
 
         Ok(())
     }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_read_item_age(metrics_collector: OpMetricsCollector) -> Result<()> {
+        metrics_collector.read_item().await?;
+
+        // Assert
+        // Fixture items are older than the largest finite bucket
+        // Looked up in the gathered family, as getting a missing series would create it
+        let age = metrics_collector.item.age.collect();
+        for state in ["active", "archived"] {
+            let login_age = |le: &str| {
+                let expected = HashMap::from([
+                    ("vault", "36vhq4xz3r6hnemzadk33evi4a"),
+                    ("category", "LOGIN"),
+                    ("state", state),
+                    ("le", le),
+                ]);
+                age[0]
+                    .get_metric()
+                    .iter()
+                    .find(|m| {
+                        m.get_label()
+                            .iter()
+                            .map(|l| (l.name(), l.value()))
+                            .collect::<HashMap<_, _>>()
+                            == expected
+                    })
+                    .unwrap_or_else(|| panic!("no {state} LOGIN bucket with le={le}"))
+                    .get_gauge()
+                    .value()
+            };
+            assert_eq!(login_age("86400.0"), 0.0);
+            assert_eq!(login_age("63072000.0"), 0.0);
+            assert_eq!(login_age("+Inf"), 1.0);
+        }
+        let buckets = metrics_collector.item.last_update_age.collect()[0]
+            .get_metric()
+            .len();
+        // DOCUMENT, LOGIN (active and archived), SECURE_NOTE and SSH_KEY
+        assert_eq!(buckets, 5 * AGE_BUCKETS.len());
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_read_item_stale(metrics_collector: OpMetricsCollector) -> Result<()> {
+        // Arrange
+        let metrics_collector = metrics_collector.with_stale_thresholds(StaleThresholds {
+            default: vec!["90d".parse().unwrap(), "10000d".parse().unwrap()],
+            per_category: HashMap::from([("LOGIN".to_string(), vec!["10000d".parse().unwrap()])]),
+        });
+
+        // Act
+        metrics_collector.read_item().await?;
+
+        // Assert
+        let stale_count = |category: &str, state: &str, threshold: &str| {
+            metrics_collector
+                .item
+                .stale_count
+                .get_metric_with_label_values(&[
+                    "36vhq4xz3r6hnemzadk33evi4a",
+                    category,
+                    state,
+                    threshold,
+                ])
+                .unwrap()
+                .get()
+        };
+        assert_eq!(stale_count("SSH_KEY", "active", "90d"), 1);
+        assert_eq!(stale_count("SSH_KEY", "active", "10000d"), 0);
+        assert_eq!(stale_count("LOGIN", "active", "10000d"), 0);
+        assert_eq!(stale_count("LOGIN", "archived", "10000d"), 0);
+        let logins: usize = metrics_collector.item.stale_count.collect()[0]
+            .get_metric()
+            .iter()
+            .filter(|m| m.get_label().iter().any(|l| l.value() == "LOGIN"))
+            .count();
+        assert_eq!(logins, 2);
+        assert_eq!(
+            metrics_collector
+                .item
+                .stale_count_per_tag
+                .get_metric_with_label_values(&["test", "active", "90d"])?
+                .get(),
+            2
+        );
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case(false, "Yuchan Lee", "lasuillard@gmail.com")]
+    #[case(true, "", "")]
+    #[tokio::test]
+    async fn test_read_item_editor(
+        metrics_collector: OpMetricsCollector,
+        #[case] editor_ids_only: bool,
+        #[case] name: &str,
+        #[case] email: &str,
+    ) -> Result<()> {
+        // Arrange
+        let metrics_collector = metrics_collector.with_editor_ids_only(editor_ids_only);
+
+        // Act
+        metrics_collector.read_item().await?;
+
+        // Assert
+        let count_per_editor = |state: &str| {
+            metrics_collector
+                .item
+                .count_per_editor
+                .get_metric_with_label_values(&[
+                    "36vhq4xz3r6hnemzadk33evi4a",
+                    "K3MAYGGYRZA2XN2AMQ5ADZJ6VI",
+                    name,
+                    email,
+                    state,
+                ])
+                .unwrap()
+                .get()
+        };
+        assert_eq!(count_per_editor("active"), 4);
+        assert_eq!(count_per_editor("archived"), 1);
+        assert_eq!(
+            metrics_collector.item.count_per_editor.collect()[0]
+                .get_metric()
+                .len(),
+            2
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_item_lists_users_after_ttl() -> Result<()> {
+        // Arrange
+        let mut command_executor = MockCommandExecutor::new();
+        command_executor
+            .expect_exec()
+            .with(eq(vec![
+                "item",
+                "list",
+                "--format",
+                "json",
+                "--include-archive",
+            ]))
+            .times(3)
+            .returning(|_| Ok(include_str!(test_dir!("fixtures/item.json")).to_string()));
+        let calls = AtomicUsize::new(0);
+        command_executor
+            .expect_exec()
+            .with(eq(vec!["user", "list", "--format", "json"]))
+            .times(2)
+            .returning(move |_| {
+                let users = include_str!(test_dir!("fixtures/user.json"));
+                // The user is renamed after the first listing
+                Ok(match calls.fetch_add(1, Ordering::SeqCst) {
+                    0 => users.to_string(),
+                    _ => users.replace("Yuchan Lee", "Yuchan L."),
+                })
+            });
+        let metrics_collector = OpMetricsCollector::new(Box::new(command_executor));
+        let editor_names = |metrics_collector: &OpMetricsCollector| -> Vec<String> {
+            metrics_collector.item.count_per_editor.collect()[0]
+                .get_metric()
+                .iter()
+                .flat_map(|m| m.get_label().iter().filter(|l| l.name() == "editor_name"))
+                .map(|l| l.value().to_string())
+                .collect()
+        };
+        metrics_collector.read_item().await?;
+
+        // Act
+        metrics_collector.read_item().await?;
+        let within_ttl = editor_names(&metrics_collector);
+        if let Some((listed_at, _)) = &mut *metrics_collector.users.lock().await {
+            *listed_at -= USERS_TTL;
+        }
+        metrics_collector.read_item().await?;
+
+        // Assert
+        assert_eq!(within_ttl, vec!["Yuchan Lee", "Yuchan Lee"]);
+        assert_eq!(
+            editor_names(&metrics_collector),
+            vec!["Yuchan L.", "Yuchan L."]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_stale_threshold_from_str() {
+        let threshold: StaleThreshold = " 1y ".parse().unwrap();
+        assert_eq!(threshold.label, "1y");
+        assert_eq!(threshold.duration, humantime::parse_duration("1y").unwrap());
+
+        assert!("forever".parse::<StaleThreshold>().is_err());
+    }
+
+    #[test]
+    fn test_observe_age() {
+        let mut counts = vec![];
+
+        observe_age(&mut counts, 0.5 * DAY);
+        observe_age(&mut counts, 10.0 * DAY);
+        observe_age(&mut counts, 1000.0 * DAY);
+
+        assert_eq!(counts, vec![1, 1, 2, 2, 2, 2, 2, 3]);
+    }
+
+    #[test]
+    fn test_age_seconds() {
+        let now = humantime::parse_rfc3339("2024-09-04T12:54:11Z").unwrap();
+
+        assert_eq!(age_seconds("2024-09-03T12:54:11Z", now), Some(DAY));
+        assert_eq!(age_seconds("2024-09-05T12:54:11Z", now), Some(0.0));
+        assert_eq!(age_seconds("yesterday", now), None);
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_read_item_removes_stale_labels(
+        metrics_collector: OpMetricsCollector,
+    ) -> Result<()> {
+        // Arrange
+        metrics_collector
+            .item
+            .count_per_tag
+            .with_label_values(&["deleted-tag", "active"])
+            .set(3);
+
+        // Act
+        metrics_collector.read_item().await?;
+
+        // Assert
+        let tags: Vec<String> = metrics_collector.item.count_per_tag.collect()[0]
+            .get_metric()
+            .iter()
+            .flat_map(|m| m.get_label().iter().filter(|l| l.name() == "tag"))
+            .map(|l| l.value().to_string())
+            .collect();
+        assert!(!tags.contains(&"deleted-tag".to_string()));
+        assert!(tags.contains(&"test".to_string()));
+
+        Ok(())
+    }
 }
@@ -1,46 +1,58 @@
-use lazy_static::lazy_static;
 #[cfg(test)]
 use mockall::predicate::*;
-use prometheus::{register_int_gauge_vec, IntGaugeVec};
-use serde::Deserialize;
-
-use super::OpMetricsCollector;
-
-lazy_static! {
-    static ref OP_SERVICEACCOUNT_RATELIMIT_USED: IntGaugeVec = register_int_gauge_vec!(
-        "op_serviceaccount_ratelimit_used",
-        "API rate limit used.",
-        &["type", "action"]
-    )
-    .unwrap();
-    static ref OP_SERVICEACCOUNT_RATELIMIT_LIMIT: IntGaugeVec = register_int_gauge_vec!(
-        "op_serviceaccount_ratelimit_limit",
-        "API rate limit.",
-        &["type", "action"]
-    )
-    .unwrap();
-    static ref OP_SERVICEACCOUNT_RATELIMIT_REMAINING: IntGaugeVec = register_int_gauge_vec!(
-        "op_serviceaccount_ratelimit_remaining",
-        "API rate limit remaining.",
-        &["type", "action"]
-    )
-    .unwrap();
-    static ref OP_SERVICEACCOUNT_RATELIMIT_RESET: IntGaugeVec = register_int_gauge_vec!(
-        "op_serviceaccount_ratelimit_reset_seconds",
-        "API rate limit remaining.",
-        &["type", "action"]
-    )
-    .unwrap();
-    static ref OP_SERVICEACCOUNT_WHOAMI: IntGaugeVec = register_int_gauge_vec!(
-        "op_serviceaccount_whoami",
-        "Current service account information.",
-        &["url", "user_uuid", "account_uuid", "user_type"]
-    )
-    .unwrap();
+use prometheus::{register_int_gauge_vec_with_registry, IntGaugeVec, Registry};
+use serde::{Deserialize, Serialize};
+
+use super::{CollectError, OpMetricsCollector};
+
+/// Metrics of the service account in use.
+pub(super) struct ServiceAccountMetrics {
+    ratelimit_used: IntGaugeVec,
+    ratelimit_limit: IntGaugeVec,
+    ratelimit_remaining: IntGaugeVec,
+    ratelimit_reset: IntGaugeVec,
+    whoami: IntGaugeVec,
+}
+
+impl ServiceAccountMetrics {
+    pub(super) fn new(registry: &Registry) -> prometheus::Result<Self> {
+        Ok(ServiceAccountMetrics {
+            ratelimit_used: register_int_gauge_vec_with_registry!(
+                "op_serviceaccount_ratelimit_used",
+                "API rate limit used.",
+                &["type", "action"],
+                registry
+            )?,
+            ratelimit_limit: register_int_gauge_vec_with_registry!(
+                "op_serviceaccount_ratelimit_limit",
+                "API rate limit.",
+                &["type", "action"],
+                registry
+            )?,
+            ratelimit_remaining: register_int_gauge_vec_with_registry!(
+                "op_serviceaccount_ratelimit_remaining",
+                "API rate limit remaining.",
+                &["type", "action"],
+                registry
+            )?,
+            ratelimit_reset: register_int_gauge_vec_with_registry!(
+                "op_serviceaccount_ratelimit_reset_seconds",
+                "Seconds until the API rate limit resets.",
+                &["type", "action"],
+                registry
+            )?,
+            whoami: register_int_gauge_vec_with_registry!(
+                "op_serviceaccount_whoami",
+                "Current service account information.",
+                &["url", "user_uuid", "account_uuid", "user_type"],
+                registry
+            )?,
+        })
+    }
 }
 
-#[derive(Deserialize, Debug)]
-struct Ratelimit {
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub(super) struct Ratelimit {
     #[serde(rename = "type")]
     pub(crate) type_: String,
     pub(crate) action: String,
@@ -50,8 +62,8 @@ struct Ratelimit {
     pub(crate) reset: i64, // Remaining seconds until the rate limit resets.
 }
 
-#[derive(Deserialize, Debug)]
-struct Whoami {
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub(super) struct Whoami {
     pub(crate) url: String,
     pub(crate) user_uuid: String,
     pub(crate) account_uuid: String,
@@ -59,37 +71,51 @@ struct Whoami {
 }
 
 impl OpMetricsCollector {
-    pub(crate) fn read_ratelimit(&self) {
+    pub(crate) async fn read_ratelimit(&self) -> Result<(), CollectError> {
         let output = self
             .command_executor
             .exec(vec!["service-account", "ratelimit", "--format", "json"])
-            .unwrap();
-        let ratelimit: Vec<Ratelimit> = serde_json::from_str(&output).unwrap();
+            .await?;
+        let ratelimit: Vec<Ratelimit> = serde_json::from_str(&output)?;
 
-        for rl in ratelimit {
-            OP_SERVICEACCOUNT_RATELIMIT_LIMIT
+        // Replace label sets of the previous collection
+        self.service_account.ratelimit_limit.reset();
+        self.service_account.ratelimit_used.reset();
+        self.service_account.ratelimit_remaining.reset();
+        self.service_account.ratelimit_reset.reset();
+        for rl in &ratelimit {
+            self.service_account
+                .ratelimit_limit
                 .with_label_values(&[&rl.type_, &rl.action])
                 .set(rl.limit);
-            OP_SERVICEACCOUNT_RATELIMIT_USED
+            self.service_account
+                .ratelimit_used
                 .with_label_values(&[&rl.type_, &rl.action])
                 .set(rl.used);
-            OP_SERVICEACCOUNT_RATELIMIT_REMAINING
+            self.service_account
+                .ratelimit_remaining
                 .with_label_values(&[&rl.type_, &rl.action])
                 .set(rl.remaining);
-            OP_SERVICEACCOUNT_RATELIMIT_RESET
+            self.service_account
+                .ratelimit_reset
                 .with_label_values(&[&rl.type_, &rl.action])
                 .set(rl.reset);
         }
+        self.snapshot.write().unwrap().ratelimit = Some(ratelimit);
+
+        Ok(())
     }
 
-    pub(crate) fn read_whoami(&self) {
+    pub(crate) async fn read_whoami(&self) -> Result<(), CollectError> {
         let output = self
             .command_executor
             .exec(vec!["whoami", "--format", "json"])
-            .unwrap();
-        let whoami: Whoami = serde_json::from_str(&output).unwrap();
+            .await?;
+        let whoami: Whoami = serde_json::from_str(&output)?;
 
-        OP_SERVICEACCOUNT_WHOAMI
+        self.service_account.whoami.reset();
+        self.service_account
+            .whoami
             .with_label_values(&[
                 &whoami.url,
                 &whoami.user_uuid,
@@ -97,6 +123,9 @@ pub(crate) fn read_whoami(&self) {
                 &whoami.user_type,
             ])
             .set(1);
+        self.snapshot.write().unwrap().whoami = Some(whoami);
+
+        Ok(())
     }
 }
 
@@ -109,78 +138,103 @@ mod tests {
     use crate::testing::metrics_collector;
 
     #[rstest]
-    fn test_read_ratelimit(metrics_collector: OpMetricsCollector) -> Result<()> {
-        metrics_collector.read_ratelimit();
+    #[tokio::test]
+    async fn test_read_ratelimit(metrics_collector: OpMetricsCollector) -> Result<()> {
+        metrics_collector.read_ratelimit().await?;
 
         // Assert
         assert_eq!(
-            OP_SERVICEACCOUNT_RATELIMIT_LIMIT
+            metrics_collector
+                .service_account
+                .ratelimit_limit
                 .get_metric_with_label_values(&["token", "write"])?
                 .get(),
             100
         );
         assert_eq!(
-            OP_SERVICEACCOUNT_RATELIMIT_USED
+            metrics_collector
+                .service_account
+                .ratelimit_used
                 .get_metric_with_label_values(&["token", "write"])?
                 .get(),
             0
         );
         assert_eq!(
-            OP_SERVICEACCOUNT_RATELIMIT_REMAINING
+            metrics_collector
+                .service_account
+                .ratelimit_remaining
                 .get_metric_with_label_values(&["token", "write"])?
                 .get(),
             100
         );
         assert_eq!(
-            OP_SERVICEACCOUNT_RATELIMIT_RESET
+            metrics_collector
+                .service_account
+                .ratelimit_reset
                 .get_metric_with_label_values(&["token", "write"])?
                 .get(),
             0
         );
         assert_eq!(
-            OP_SERVICEACCOUNT_RATELIMIT_LIMIT
+            metrics_collector
+                .service_account
+                .ratelimit_limit
                 .get_metric_with_label_values(&["token", "read"])?
                 .get(),
             1000
         );
         assert_eq!(
-            OP_SERVICEACCOUNT_RATELIMIT_USED
+            metrics_collector
+                .service_account
+                .ratelimit_used
                 .get_metric_with_label_values(&["token", "read"])?
                 .get(),
             1
         );
         assert_eq!(
-            OP_SERVICEACCOUNT_RATELIMIT_REMAINING
+            metrics_collector
+                .service_account
+                .ratelimit_remaining
                 .get_metric_with_label_values(&["token", "read"])?
                 .get(),
             999
         );
         assert_eq!(
-            OP_SERVICEACCOUNT_RATELIMIT_RESET
+            metrics_collector
+                .service_account
+                .ratelimit_reset
                 .get_metric_with_label_values(&["token", "read"])?
                 .get(),
             308
         );
         assert_eq!(
-            OP_SERVICEACCOUNT_RATELIMIT_LIMIT
+            metrics_collector
+                .service_account
+                .ratelimit_limit
                 .get_metric_with_label_values(&["account", "read_write"])?
                 .get(),
             1000
         );
         assert_eq!(
-            OP_SERVICEACCOUNT_RATELIMIT_USED
+            metrics_collector
+                .service_account
+                .ratelimit_used
                 .get_metric_with_label_values(&["account", "read_write"])?
                 .get(),
             1
         );
         assert_eq!(
-            OP_SERVICEACCOUNT_RATELIMIT_REMAINING
+            metrics_collector
+                .service_account
+                .ratelimit_remaining
                 .get_metric_with_label_values(&["account", "read_write"])?
                 .get(),
             999
         );
         assert_eq!(
-            OP_SERVICEACCOUNT_RATELIMIT_RESET
+            metrics_collector
+                .service_account
+                .ratelimit_reset
                 .get_metric_with_label_values(&["account", "read_write"])?
                 .get(),
             83108
@@ -190,12 +244,15 @@ fn test_read_ratelimit(metrics_collector: OpMetricsCollector) -> Result<()> {
     }
 
     #[rstest]
-    fn test_read_whoami(metrics_collector: OpMetricsCollector) -> Result<()> {
-        metrics_collector.read_whoami();
+    #[tokio::test]
+    async fn test_read_whoami(metrics_collector: OpMetricsCollector) -> Result<()> {
+        metrics_collector.read_whoami().await?;
 
         // Assert
         assert_eq!(
-            OP_SERVICEACCOUNT_WHOAMI
+            metrics_collector
+                .service_account
+                .whoami
                 .get_metric_with_label_values(&[
                     "https://my.1password.com",
                     "!!!!!!!!!!!!!!!!!!!!!!!!!!",
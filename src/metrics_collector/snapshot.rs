@@ -0,0 +1,110 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::Serialize;
+
+use super::{account::Account,
+            service_account::{Ratelimit, Whoami},
+            OpMetricsCollector, ACTIVE};
+
+/// Non-secret metadata of the last collection of each collector, `None` if never collected.
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub(crate) struct Snapshot {
+    pub(super) account: Option<Account>,
+    pub(super) whoami: Option<Whoami>,
+    pub(super) ratelimit: Option<Vec<Ratelimit>>,
+    pub(super) vaults: Option<Vec<VaultSummary>>,
+    pub(super) items: Option<ItemSummary>,
+    pub(super) documents: Option<DocumentSummary>,
+    pub(super) group_count: Option<usize>,
+    pub(super) user_count: Option<usize>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub(crate) struct VaultSummary {
+    pub(super) id: String,
+    pub(super) name: String,
+    /// Number of active items in the vault, if items collected.
+    pub(super) item_count: Option<i64>,
+}
+
+/// Counts of items. Aggregates are of active items only.
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub(crate) struct ItemSummary {
+    pub(super) active: usize,
+    pub(super) archived: usize,
+    pub(super) per_vault: BTreeMap<String, i64>,
+    pub(super) per_category: BTreeMap<String, i64>,
+    pub(super) per_tag: BTreeMap<String, i64>,
+}
+
+/// Counts and sizes of documents. Aggregates are of active documents only.
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub(crate) struct DocumentSummary {
+    pub(super) active: usize,
+    pub(super) archived: usize,
+    pub(super) per_vault: BTreeMap<String, i64>,
+    pub(super) per_tag: BTreeMap<String, i64>,
+    pub(super) file_size_per_vault_bytes: BTreeMap<String, i64>,
+    pub(super) file_size_per_tag_bytes: BTreeMap<String, i64>,
+}
+
+/// Counts of active ones out of counts per key and state, sorted by key for stable output.
+pub(super) fn active_counts(counts: &HashMap<(String, &str), i64>) -> BTreeMap<String, i64> {
+    counts
+        .iter()
+        .filter(|((_, state), _)| *state == ACTIVE)
+        .map(|((key, _), count)| (key.clone(), *count))
+        .collect()
+}
+
+impl OpMetricsCollector {
+    /// Returns metadata of the last collection, with item counts joined to vaults.
+    pub(crate) fn snapshot(&self) -> Snapshot {
+        let mut snapshot = self.snapshot.read().unwrap().clone();
+        if let (Some(vaults), Some(items)) = (&mut snapshot.vaults, &snapshot.items) {
+            for vault in vaults {
+                vault.item_count = Some(items.per_vault.get(&vault.id).copied().unwrap_or(0));
+            }
+        }
+
+        snapshot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+
+    use super::*;
+    use crate::{metrics_collector::Metrics, testing::metrics_collector};
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_snapshot(metrics_collector: OpMetricsCollector) {
+        // Arrange
+        assert_eq!(metrics_collector.snapshot(), Snapshot::default());
+
+        // Act
+        metrics_collector
+            .collect(vec![Metrics::Vault, Metrics::Item, Metrics::Group])
+            .await;
+
+        // Assert
+        let snapshot = metrics_collector.snapshot();
+        assert_eq!(
+            snapshot.vaults,
+            Some(vec![VaultSummary {
+                id: "36vhq4xz3r6hnemzadk33evi4a".to_string(),
+                name: "Testing 金庫".to_string(),
+                item_count: Some(4),
+            }])
+        );
+        let items = snapshot.items.unwrap();
+        assert_eq!((items.active, items.archived), (4, 1));
+        assert_eq!(items.per_category["LOGIN"], 1);
+        assert_eq!(items.per_tag["test"], 3);
+        assert_eq!(snapshot.group_count, Some(4));
+        assert_eq!(snapshot.account, None);
+        assert_eq!(snapshot.documents, None);
+    }
+}
@@ -1,23 +1,35 @@
-use lazy_static::lazy_static;
+use std::{collections::HashMap,
+          time::{Duration, Instant}};
+
 #[cfg(test)]
 use mockall::predicate::*;
-use prometheus::{register_int_gauge_vec, IntGaugeVec};
+use prometheus::{register_int_gauge_vec_with_registry, IntGaugeVec, Registry};
 use serde::Deserialize;
 
-use super::OpMetricsCollector;
+use super::{CollectError, OpMetricsCollector, USERS_TTL};
 
-lazy_static! {
-    static ref OP_USER_TOTAL: IntGaugeVec =
-        register_int_gauge_vec!("op_user_count_total", "Total number of users.", &[]).unwrap();
+/// Metrics of users.
+pub(super) struct UserMetrics {
+    count_total: IntGaugeVec,
 }
 
-#[derive(Deserialize, Debug)]
-struct User {
-    #[allow(dead_code)]
+impl UserMetrics {
+    pub(super) fn new(registry: &Registry) -> prometheus::Result<Self> {
+        Ok(UserMetrics {
+            count_total: register_int_gauge_vec_with_registry!(
+                "op_user_count_total",
+                "Total number of users.",
+                &[],
+                registry
+            )?,
+        })
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub(super) struct User {
     pub(crate) id: String,
-    #[allow(dead_code)]
     pub(crate) name: String,
-    #[allow(dead_code)]
     pub(crate) email: String,
     #[allow(dead_code)]
     #[serde(rename = "type")]
@@ -27,17 +39,55 @@ struct User {
 }
 
 impl OpMetricsCollector {
-    pub(crate) fn read_user(&self) {
+    pub(crate) async fn read_user(&self) -> Result<(), CollectError> {
+        // Listed on each collection, unless another one is listing them already
+        let count = self.list_users_by_id(Duration::ZERO).await?.len();
+
+        self.user
+            .count_total
+            .with_label_values::<&str>(&[])
+            .set(count as i64);
+        self.snapshot.write().unwrap().user_count = Some(count);
+
+        Ok(())
+    }
+
+    async fn list_users(&self) -> Result<Vec<User>, CollectError> {
         let output = self
             .command_executor
             .exec(vec!["user", "list", "--format", "json"])
-            .unwrap();
-        let users: Vec<User> = serde_json::from_str(&output).unwrap();
+            .await?;
 
-        OP_USER_TOTAL
-            .with_label_values::<&str>(&[])
-            .set(users.len() as i64);
+        Ok(serde_json::from_str(&output)?)
+    }
+
+    /// Returns users by ID to resolve editors of items, listing them if not listed within `USERS_TTL`.
+    pub(super) async fn users_by_id(&self) -> Result<HashMap<String, User>, CollectError> {
+        self.list_users_by_id(USERS_TTL).await
     }
+
+    /// Returns users by ID, reusing the last listing if made within given TTL or while waiting for it.
+    async fn list_users_by_id(&self, ttl: Duration) -> Result<HashMap<String, User>, CollectError> {
+        let requested_at = Instant::now();
+        // Held across the listing so concurrent callers wait for one listing instead of starting their own
+        let mut cached = self.users.lock().await;
+        if let Some((listed_at, users)) = &*cached {
+            if *listed_at >= requested_at || listed_at.elapsed() < ttl {
+                return Ok(users.clone());
+            }
+        }
+
+        let users = by_id(self.list_users().await?);
+        *cached = Some((Instant::now(), users.clone()));
+        Ok(users)
+    }
+}
+
+fn by_id(users: Vec<User>) -> HashMap<String, User> {
+    users
+        .into_iter()
+        .map(|user| (user.id.clone(), user))
+        .collect()
 }
 
 #[cfg(test)]
@@ -46,15 +96,45 @@ mod tests {
     use rstest::*;
 
     use super::*;
-    use crate::testing::metrics_collector;
+    use crate::{command_executor::MockCommandExecutor, test_dir, testing::metrics_collector};
 
     #[rstest]
-    fn test_read_user(metrics_collector: OpMetricsCollector) -> Result<()> {
-        metrics_collector.read_user();
+    #[tokio::test]
+    async fn test_read_user(metrics_collector: OpMetricsCollector) -> Result<()> {
+        metrics_collector.read_user().await?;
+
+        // Assert
+        assert_eq!(
+            metrics_collector
+                .user
+                .count_total
+                .get_metric_with_label_values::<&str>(&[])?
+                .get(),
+            1
+        );
+
+        Ok(())
+    }
+    #[tokio::test]
+    async fn test_read_user_waits_for_in_flight_listing() -> Result<()> {
+        // Arrange
+        let metrics_collector = OpMetricsCollector::new(Box::new(MockCommandExecutor::new()));
+        let users: Vec<User> = serde_json::from_str(include_str!(test_dir!("fixtures/user.json")))?;
+        let mut listing = metrics_collector.users.lock().await;
+
+        // Act
+        let (result, _) = tokio::join!(metrics_collector.read_user(), async move {
+            // Listing completes after the user collection asked for users
+            tokio::task::yield_now().await;
+            *listing = Some((Instant::now(), by_id(users)));
+        });
 
         // Assert
+        result?;
         assert_eq!(
-            OP_USER_TOTAL
+            metrics_collector
+                .user
+                .count_total
                 .get_metric_with_label_values::<&str>(&[])?
                 .get(),
             1
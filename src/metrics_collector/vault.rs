@@ -1,37 +1,76 @@
-use lazy_static::lazy_static;
 #[cfg(test)]
 use mockall::predicate::*;
-use prometheus::{register_int_gauge_vec, IntGaugeVec};
+use prometheus::{register_int_gauge_vec_with_registry, IntGaugeVec, Registry};
 use serde::Deserialize;
 
-use super::OpMetricsCollector;
+use super::{snapshot::VaultSummary, CollectError, OpMetricsCollector};
 
-lazy_static! {
-    static ref OP_VAULT_TOTAL: IntGaugeVec =
-        register_int_gauge_vec!("op_vault_count_total", "Total number of vaults.", &[]).unwrap();
+/// Metrics of vaults.
+pub(super) struct VaultMetrics {
+    count_total: IntGaugeVec,
+    info: IntGaugeVec,
+}
+
+impl VaultMetrics {
+    pub(super) fn new(registry: &Registry) -> prometheus::Result<Self> {
+        Ok(VaultMetrics {
+            count_total: register_int_gauge_vec_with_registry!(
+                "op_vault_count_total",
+                "Total number of vaults.",
+                &[],
+                registry
+            )?,
+            info: register_int_gauge_vec_with_registry!(
+                "op_vault_info",
+                "Vault information, always 1. Join on `vault` to get names of vaults.",
+                &["vault", "vault_name"],
+                registry
+            )?,
+        })
+    }
 }
 
 #[derive(Deserialize, Debug)]
 struct Vault {
-    #[allow(dead_code)]
     pub(crate) id: String,
-    #[allow(dead_code)]
     pub(crate) name: String,
     #[allow(dead_code)]
     pub(crate) content_version: i32,
 }
 
 impl OpMetricsCollector {
-    pub(crate) fn read_vault(&self) {
+    pub(crate) async fn read_vault(&self) -> Result<(), CollectError> {
         let output = self
             .command_executor
             .exec(vec!["vault", "list", "--format", "json"])
-            .unwrap();
-        let vaults: Vec<Vault> = serde_json::from_str(&output).unwrap();
+            .await?;
+        let vaults: Vec<Vault> = serde_json::from_str(&output)?;
 
-        OP_VAULT_TOTAL
+        self.vault
+            .count_total
             .with_label_values::<&str>(&[])
             .set(vaults.len() as i64);
+
+        // Replace label sets of the previous collection, for renamed or removed vaults
+        self.vault.info.reset();
+        for vault in &vaults {
+            self.vault
+                .info
+                .with_label_values(&[vault.id.as_str(), vault.name.as_str()])
+                .set(1);
+        }
+        self.snapshot.write().unwrap().vaults = Some(
+            vaults
+                .into_iter()
+                .map(|vault| VaultSummary {
+                    id: vault.id,
+                    name: vault.name,
+                    item_count: None,
+                })
+                .collect(),
+        );
+
+        Ok(())
     }
 }
 
@@ -44,16 +83,27 @@ mod tests {
     use crate::testing::metrics_collector;
 
     #[rstest]
-    fn test_read_vault(metrics_collector: OpMetricsCollector) -> Result<()> {
-        metrics_collector.read_vault();
+    #[tokio::test]
+    async fn test_read_vault(metrics_collector: OpMetricsCollector) -> Result<()> {
+        metrics_collector.read_vault().await?;
 
         // Assert
         assert_eq!(
-            OP_VAULT_TOTAL
+            metrics_collector
+                .vault
+                .count_total
                 .get_metric_with_label_values::<&str>(&[])?
                 .get(),
             1
         );
+        assert_eq!(
+            metrics_collector
+                .vault
+                .info
+                .get_metric_with_label_values(&["36vhq4xz3r6hnemzadk33evi4a", "Testing 金庫"])?
+                .get(),
+            1
+        );
 
         Ok(())
     }
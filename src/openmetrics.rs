@@ -0,0 +1,370 @@
+use std::fmt::Write;
+
+use prometheus::proto::{Metric, MetricFamily, MetricType};
+
+pub(crate) const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// OpenMetrics type of a gauge family, which the Prometheus text format cannot express.
+enum GaugeType {
+    Info,
+    /// Stateset of the states while the gauge is non-zero and zero, respectively.
+    StateSet([&'static str; 2]),
+}
+
+/// Gauge families exposed with an OpenMetrics type other than `gauge`.
+const GAUGE_TYPES: &[(&str, GaugeType)] = &[
+    ("op_account_current", GaugeType::Info),
+    ("op_exporter_buildinfo", GaugeType::Info),
+    (
+        "op_exporter_collector_success",
+        GaugeType::StateSet(["success", "failure"]),
+    ),
+    ("op_serviceaccount_whoami", GaugeType::Info),
+    ("op_vault_info", GaugeType::Info),
+];
+
+/// Encode metric families in the OpenMetrics text format.
+///
+/// Gauges listed in [`GAUGE_TYPES`] are exposed as info metrics or statesets, gauges named `*_bucket` with `le` label as gauge histograms, and families named with a `_seconds` or `_bytes` suffix get a unit.
+pub(crate) fn encode(metric_families: &[MetricFamily]) -> String {
+    let mut buf = String::new();
+    for mf in metric_families {
+        let name = mf.name();
+        let gauge_type = GAUGE_TYPES
+            .iter()
+            .find(|(family, _)| *family == name)
+            .map(|(_, gauge_type)| gauge_type)
+            .filter(|_| mf.get_field_type() == MetricType::GAUGE);
+        let (family, metric_type) = match mf.get_field_type() {
+            // Counter samples are suffixed with `_total`, but not the family
+            MetricType::COUNTER => (name.strip_suffix("_total").unwrap_or(name), "counter"),
+            MetricType::GAUGE => {
+                if let Some(GaugeType::Info) = gauge_type {
+                    // Info samples are suffixed with `_info`, but not the family
+                    (name.strip_suffix("_info").unwrap_or(name), "info")
+                } else if let Some(GaugeType::StateSet(_)) = gauge_type {
+                    (name, "stateset")
+                } else if let Some(family) = name.strip_suffix("_bucket").filter(|_| has_le(mf)) {
+                    (family, "gaugehistogram")
+                } else {
+                    (name, "gauge")
+                }
+            }
+            MetricType::HISTOGRAM => (name, "histogram"),
+            MetricType::SUMMARY => (name, "summary"),
+            MetricType::UNTYPED => (name, "unknown"),
+        };
+
+        writeln!(buf, "# TYPE {family} {metric_type}").unwrap();
+        if let Some(unit) = unit(family) {
+            writeln!(buf, "# UNIT {family} {unit}").unwrap();
+        }
+        if !mf.help().is_empty() {
+            writeln!(buf, "# HELP {family} {}", escape(mf.help())).unwrap();
+        }
+
+        let mut metrics: Vec<&Metric> = mf.get_metric().iter().collect();
+        if metric_type == "gaugehistogram" {
+            // Buckets of a label set are grouped together, in increasing order of `le`
+            metrics.sort_by(|a, b| {
+                let key = |m: &Metric| -> (Vec<(String, String)>, f64) {
+                    let labels = m.get_label().iter().filter(|l| l.name() != "le");
+                    let le = m.get_label().iter().find(|l| l.name() == "le");
+                    (
+                        labels
+                            .map(|l| (l.name().to_string(), l.value().to_string()))
+                            .collect(),
+                        le.and_then(|l| l.value().parse().ok()).unwrap_or(f64::NAN),
+                    )
+                };
+                let (a, b) = (key(a), key(b));
+                a.0.cmp(&b.0).then(a.1.total_cmp(&b.1))
+            });
+        }
+        for m in metrics {
+            match mf.get_field_type() {
+                MetricType::COUNTER => {
+                    write_sample(&mut buf, family, "_total", m, None, m.get_counter().value())
+                }
+                MetricType::GAUGE => match gauge_type {
+                    Some(GaugeType::Info) => {
+                        write_sample(&mut buf, family, "_info", m, None, m.get_gauge().value())
+                    }
+                    Some(GaugeType::StateSet(states)) => {
+                        // Exactly one of the states is set, labeled by the family name
+                        let set = m.get_gauge().value() != 0.0;
+                        for (state, value) in states.iter().zip([set, !set]) {
+                            let label = Some((family, *state));
+                            write_sample(
+                                &mut buf,
+                                family,
+                                "",
+                                m,
+                                label,
+                                f64::from(u8::from(value)),
+                            );
+                        }
+                    }
+                    // Gauge histogram buckets are named as is
+                    None => write_sample(&mut buf, name, "", m, None, m.get_gauge().value()),
+                },
+                MetricType::HISTOGRAM => {
+                    let h = m.get_histogram();
+                    let mut inf_seen = false;
+                    for b in h.get_bucket() {
+                        inf_seen |= b.upper_bound() == f64::INFINITY;
+                        let le = format_bound(b.upper_bound());
+                        let count = b.cumulative_count() as f64;
+                        write_sample(&mut buf, family, "_bucket", m, Some(("le", &le)), count);
+                    }
+                    if !inf_seen {
+                        let count = h.sample_count() as f64;
+                        write_sample(&mut buf, family, "_bucket", m, Some(("le", "+Inf")), count);
+                    }
+                    write_sample(&mut buf, family, "_sum", m, None, h.sample_sum());
+                    write_sample(&mut buf, family, "_count", m, None, h.sample_count() as f64);
+                }
+                MetricType::SUMMARY => {
+                    let s = m.get_summary();
+                    for q in s.get_quantile() {
+                        let quantile = format_bound(q.quantile());
+                        write_sample(
+                            &mut buf,
+                            family,
+                            "",
+                            m,
+                            Some(("quantile", &quantile)),
+                            q.value(),
+                        );
+                    }
+                    write_sample(&mut buf, family, "_sum", m, None, s.sample_sum());
+                    write_sample(&mut buf, family, "_count", m, None, s.sample_count() as f64);
+                }
+                MetricType::UNTYPED => {
+                    write_sample(&mut buf, family, "", m, None, m.untyped.value())
+                }
+            }
+        }
+    }
+    buf.push_str("# EOF\n");
+
+    buf
+}
+
+/// Whether metrics of the family are labeled with bucket upper bounds.
+fn has_le(mf: &MetricFamily) -> bool {
+    mf.get_metric()
+        .iter()
+        .any(|m| m.get_label().iter().any(|l| l.name() == "le"))
+}
+
+/// Unit of a metric family, taken from its name suffix.
+fn unit(family: &str) -> Option<&'static str> {
+    ["seconds", "bytes"]
+        .into_iter()
+        .find(|unit| family.ends_with(&format!("_{unit}")))
+}
+
+fn write_sample(
+    buf: &mut String,
+    name: &str,
+    suffix: &str,
+    m: &Metric,
+    additional_label: Option<(&str, &str)>,
+    value: f64,
+) {
+    buf.push_str(name);
+    buf.push_str(suffix);
+
+    let labels: Vec<String> = m
+        .get_label()
+        .iter()
+        .map(|l| (l.name(), l.value()))
+        .chain(additional_label)
+        .map(|(name, value)| match name {
+            // Bucket bounds in the canonical float form, e.g. `60.0`
+            "le" => (
+                name,
+                value
+                    .parse()
+                    .map_or_else(|_| value.to_string(), format_bound),
+            ),
+            _ => (name, value.to_string()),
+        })
+        .map(|(name, value)| format!("{name}=\"{}\"", escape(&value)))
+        .collect();
+    if !labels.is_empty() {
+        write!(buf, "{{{}}}", labels.join(",")).unwrap();
+    }
+
+    writeln!(buf, " {}", format_value(value)).unwrap();
+}
+
+fn format_value(value: f64) -> String {
+    if value == f64::INFINITY {
+        "+Inf".to_string()
+    } else if value == f64::NEG_INFINITY {
+        "-Inf".to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+/// Format a bucket bound or quantile as a float, which OpenMetrics requires even for integral values.
+fn format_bound(value: f64) -> String {
+    if value.is_infinite() {
+        format_value(value)
+    } else {
+        format!("{value:?}")
+    }
+}
+
+/// Escape label value or help text.
+fn escape(s: &str) -> String {
+    s.replace('\\', r"\\")
+        .replace('\n', r"\n")
+        .replace('"', r#"\""#)
+}
+
+#[cfg(test)]
+mod tests {
+    use prometheus::{register_histogram_vec_with_registry, register_int_counter_vec_with_registry,
+                     register_int_gauge_vec_with_registry, register_int_gauge_with_registry,
+                     Registry};
+
+    use super::*;
+
+    #[test]
+    fn test_encode() -> anyhow::Result<()> {
+        // Arrange
+        let registry = Registry::new();
+        register_int_counter_vec_with_registry!(
+            "op_exporter_collector_errors_total",
+            "Errors.",
+            &["collector"],
+            registry
+        )?
+        .with_label_values(&["item"])
+        .inc();
+        register_int_gauge_vec_with_registry!(
+            "op_vault_info",
+            "Vault \"information\".",
+            &["vault", "vault_name"],
+            registry
+        )?
+        .with_label_values(&["abc", "Private\\Shared"])
+        .set(1);
+        register_int_gauge_with_registry!(
+            "op_serviceaccount_ratelimit_reset_seconds",
+            "Reset.",
+            registry
+        )?
+        .set(30);
+        register_histogram_vec_with_registry!(
+            "op_item_age_seconds",
+            "Age.",
+            &["vault"],
+            vec![60.0, 3600.0],
+            registry
+        )?
+        .with_label_values(&["abc"])
+        .observe(120.0);
+        let buckets = register_int_gauge_vec_with_registry!(
+            "op_item_last_update_age_seconds_bucket",
+            "Last update age.",
+            &["vault", "le"],
+            registry
+        )?;
+        for (vault, le, count) in [
+            ("abc", "+Inf", 2),
+            ("abc", "60", 1),
+            ("def", "60", 0),
+            ("abc", "600", 1),
+            ("def", "+Inf", 1),
+            ("def", "600", 1),
+        ] {
+            buckets.with_label_values(&[vault, le]).set(count);
+        }
+
+        // Act
+        let text = encode(&registry.gather());
+
+        // Assert
+        assert_eq!(
+            text,
+            r#"# TYPE op_exporter_collector_errors counter
+# HELP op_exporter_collector_errors Errors.
+op_exporter_collector_errors_total{collector="item"} 1
+# TYPE op_item_age_seconds histogram
+# UNIT op_item_age_seconds seconds
+# HELP op_item_age_seconds Age.
+op_item_age_seconds_bucket{vault="abc",le="60.0"} 0
+op_item_age_seconds_bucket{vault="abc",le="3600.0"} 1
+op_item_age_seconds_bucket{vault="abc",le="+Inf"} 1
+op_item_age_seconds_sum{vault="abc"} 120
+op_item_age_seconds_count{vault="abc"} 1
+# TYPE op_item_last_update_age_seconds gaugehistogram
+# UNIT op_item_last_update_age_seconds seconds
+# HELP op_item_last_update_age_seconds Last update age.
+op_item_last_update_age_seconds_bucket{le="60.0",vault="abc"} 1
+op_item_last_update_age_seconds_bucket{le="600.0",vault="abc"} 1
+op_item_last_update_age_seconds_bucket{le="+Inf",vault="abc"} 2
+op_item_last_update_age_seconds_bucket{le="60.0",vault="def"} 0
+op_item_last_update_age_seconds_bucket{le="600.0",vault="def"} 1
+op_item_last_update_age_seconds_bucket{le="+Inf",vault="def"} 1
+# TYPE op_serviceaccount_ratelimit_reset_seconds gauge
+# UNIT op_serviceaccount_ratelimit_reset_seconds seconds
+# HELP op_serviceaccount_ratelimit_reset_seconds Reset.
+op_serviceaccount_ratelimit_reset_seconds 30
+# TYPE op_vault info
+# HELP op_vault Vault \"information\".
+op_vault_info{vault="abc",vault_name="Private\\Shared"} 1
+# EOF
+"#
+        );
+
+        Ok(())
+    }
+    #[test]
+    fn test_encode_info_and_stateset() -> anyhow::Result<()> {
+        // Arrange
+        let registry = Registry::new();
+        register_int_gauge_vec_with_registry!(
+            "op_account_current",
+            "Account.",
+            &["id", "state"],
+            registry
+        )?
+        .with_label_values(&["abc", "ACTIVE"])
+        .set(1);
+        let success = register_int_gauge_vec_with_registry!(
+            "op_exporter_collector_success",
+            "Success.",
+            &["collector"],
+            registry
+        )?;
+        success.with_label_values(&["item"]).set(1);
+        success.with_label_values(&["user"]).set(0);
+
+        // Act
+        let text = encode(&registry.gather());
+
+        // Assert
+        assert_eq!(
+            text,
+            r#"# TYPE op_account_current info
+# HELP op_account_current Account.
+op_account_current_info{id="abc",state="ACTIVE"} 1
+# TYPE op_exporter_collector_success stateset
+# HELP op_exporter_collector_success Success.
+op_exporter_collector_success{collector="item",op_exporter_collector_success="success"} 1
+op_exporter_collector_success{collector="item",op_exporter_collector_success="failure"} 0
+op_exporter_collector_success{collector="user",op_exporter_collector_success="success"} 0
+op_exporter_collector_success{collector="user",op_exporter_collector_success="failure"} 1
+# EOF
+"#
+        );
+
+        Ok(())
+    }
+}
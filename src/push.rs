@@ -0,0 +1,148 @@
+use std::{collections::HashMap, future::Future, time::Duration};
+
+use prometheus::BasicAuthentication;
+use tokio::{signal::unix::{signal, SignalKind},
+            time::MissedTickBehavior};
+
+use crate::{collect::{collect_once, OneShotError},
+            config::Secret,
+            metrics_collector::{Metrics, OpMetricsCollector}};
+
+/// Options of the push mode.
+#[derive(clap::Args, Debug)]
+pub(crate) struct PushArgs {
+    /// URL of the Pushgateway, e.g. `http://pushgateway:9091`.
+    #[arg(long, env = "OP_EXPORTER_PUSHGATEWAY_URL")]
+    pushgateway_url: String,
+
+    /// Job label to group pushed metrics by.
+    #[arg(long, env = "OP_EXPORTER_PUSH_JOB", default_value = env!("CARGO_PKG_NAME"))]
+    job: String,
+
+    /// Instance label to group pushed metrics by, in addition to the job.
+    #[arg(long, env = "OP_EXPORTER_PUSH_INSTANCE")]
+    instance: Option<String>,
+
+    /// Keep running and push every given interval, e.g. `5m`. Pushes once and exits if not set.
+    #[arg(long, env = "OP_EXPORTER_PUSH_INTERVAL", value_parser = crate::parse_interval)]
+    push_interval: Option<Duration>,
+
+    /// Username for basic authentication to the Pushgateway.
+    #[arg(
+        long,
+        env = "OP_EXPORTER_PUSHGATEWAY_USERNAME",
+        requires = "pushgateway_password"
+    )]
+    pushgateway_username: Option<String>,
+
+    /// Password for basic authentication to the Pushgateway.
+    #[arg(
+        long,
+        env = "OP_EXPORTER_PUSHGATEWAY_PASSWORD",
+        hide_env_values = true,
+        requires = "pushgateway_username"
+    )]
+    pushgateway_password: Option<Secret>,
+}
+
+/// Collect given metrics and push them to the Pushgateway, once or repeatedly until terminated.
+///
+/// On termination, the in-flight collection and push are given up to `shutdown_timeout` to finish.
+pub(crate) async fn run(
+    metrics_collector: OpMetricsCollector,
+    metrics: Vec<Metrics>,
+    args: &PushArgs,
+    shutdown_timeout: Duration,
+) -> Result<(), OneShotError> {
+    let Some(push_interval) = args.push_interval else {
+        return push_once(&metrics_collector, metrics, args).await;
+    };
+
+    log::info!(
+        "Pushing to {} every {}",
+        args.pushgateway_url,
+        humantime::format_duration(push_interval)
+    );
+    let mut terminate = signal(SignalKind::terminate()).expect("failed to install SIGTERM handler");
+    let mut interval = tokio::time::interval(push_interval);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                let push = push_once(&metrics_collector, metrics.clone(), args);
+                tokio::pin!(push);
+                let result = tokio::select! {
+                    result = &mut push => result,
+                    _ = terminate.recv() => break finish(push, shutdown_timeout).await,
+                    _ = tokio::signal::ctrl_c() => break finish(push, shutdown_timeout).await,
+                };
+                // Keep trying on failure, the next push may succeed
+                if let Err(err) = result {
+                    log::error!("{}", err);
+                }
+            }
+            _ = terminate.recv() => break,
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+    log::info!("Stopped pushing");
+
+    Ok(())
+}
+
+/// Wait up to given timeout for the in-flight push on shutdown, then abandon it.
+async fn finish(push: impl Future<Output = Result<(), OneShotError>>, shutdown_timeout: Duration) {
+    log::info!(
+        "Shutting down, waiting up to {} for the in-flight push",
+        humantime::format_duration(shutdown_timeout)
+    );
+    match tokio::time::timeout(shutdown_timeout, push).await {
+        Ok(Ok(())) => {}
+        Ok(Err(err)) => log::error!("{}", err),
+        // Dropping the collection kills running 1Password CLI processes
+        Err(_) => log::warn!("Abandoning push not finished in time"),
+    }
+}
+
+async fn push_once(
+    metrics_collector: &OpMetricsCollector,
+    metrics: Vec<Metrics>,
+    args: &PushArgs,
+) -> Result<(), OneShotError> {
+    let collected = collect_once(metrics_collector, metrics).await;
+
+    let metric_families = metrics_collector.registry().gather();
+    let mut grouping = HashMap::new();
+    if let Some(instance) = &args.instance {
+        grouping.insert("instance".to_string(), instance.clone());
+    }
+    let basic_auth = args
+        .pushgateway_username
+        .clone()
+        .zip(args.pushgateway_password.clone())
+        .map(|(username, password)| BasicAuthentication {
+            username,
+            password: password.into_inner(),
+        });
+    let job = args.job.clone();
+    let url = args.pushgateway_url.clone();
+
+    // Pushing blocks on HTTP request. On a dedicated thread rather than the blocking pool, which
+    // the runtime waits for on exit, so an abandoned push does not hold up shutdown.
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    std::thread::spawn(move || {
+        let _ = tx.send(prometheus::push_metrics(
+            &job,
+            grouping,
+            &url,
+            metric_families,
+            basic_auth,
+        ));
+    });
+    rx.await
+        .map_err(|err| OneShotError::Push(err.to_string()))?
+        .map_err(|err| OneShotError::Push(err.to_string()))?;
+    log::info!("Pushed metrics to {}", args.pushgateway_url);
+
+    collected
+}
@@ -0,0 +1,243 @@
+use std::{collections::HashMap,
+          sync::{Arc, RwLock},
+          time::{Duration, SystemTime}};
+
+use prometheus::proto::MetricFamily;
+use tokio::{sync::watch, task::JoinHandle, time::MissedTickBehavior};
+
+use crate::metrics_collector::{merge_metric_families, Metrics, OpMetricsCollector};
+
+/// Status of the recent collections of a collector.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct CollectorStatus {
+    pub(crate) last_attempt: Option<SystemTime>,
+    pub(crate) last_success: Option<SystemTime>,
+    /// Error of the last collection, if it failed.
+    pub(crate) last_error: Option<String>,
+}
+
+/// Collects metrics periodically in background and keeps the last snapshot for serving.
+pub(crate) struct Scheduler {
+    metrics_collector: OpMetricsCollector,
+    /// Metrics to collect, grouped by their collection interval.
+    schedule: Vec<(Duration, Vec<Metrics>)>,
+    metric_families: RwLock<Vec<MetricFamily>>,
+    statuses: RwLock<HashMap<Metrics, CollectorStatus>>,
+    /// Notified after each collection.
+    collected: watch::Sender<()>,
+}
+
+impl Scheduler {
+    /// Create a scheduler collecting `metrics` every `interval`, unless overridden in `intervals`.
+    pub(crate) fn new(
+        metrics_collector: OpMetricsCollector,
+        metrics: Vec<Metrics>,
+        interval: Duration,
+        intervals: &HashMap<Metrics, Duration>,
+    ) -> Self {
+        let mut schedule: Vec<(Duration, Vec<Metrics>)> = vec![];
+        for metric in metrics {
+            let interval = intervals.get(&metric).copied().unwrap_or(interval);
+            match schedule.iter_mut().find(|(i, _)| *i == interval) {
+                Some((_, group)) => group.push(metric),
+                None => schedule.push((interval, vec![metric])),
+            }
+        }
+
+        Scheduler {
+            metrics_collector,
+            schedule,
+            metric_families: RwLock::new(vec![]),
+            statuses: RwLock::new(HashMap::new()),
+            collected: watch::Sender::new(()),
+        }
+    }
+
+    /// Spawn a task per distinct interval collecting its metrics, starting immediately.
+    pub(crate) fn start(self: &Arc<Self>) -> Vec<JoinHandle<()>> {
+        self.schedule
+            .iter()
+            .map(|(interval, metrics)| {
+                log::info!(
+                    "Collecting {:?} every {}",
+                    metrics,
+                    humantime::format_duration(*interval)
+                );
+                let scheduler = self.clone();
+                let mut interval = tokio::time::interval(*interval);
+                let metrics = metrics.clone();
+                tokio::spawn(async move {
+                    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+                    loop {
+                        interval.tick().await;
+                        scheduler.collect(metrics.clone()).await;
+                    }
+                })
+            })
+            .collect()
+    }
+
+    /// Run a collection of given metrics and refresh the snapshot with its result.
+    pub(crate) async fn collect(&self, metrics: Vec<Metrics>) {
+        log::debug!("Collecting metrics: {:?}", metrics);
+        let results = self.metrics_collector.collect(metrics.clone()).await;
+
+        let now = SystemTime::now();
+        let mut statuses = self.statuses.write().unwrap();
+        for (metric, result) in results {
+            let status = statuses.entry(metric).or_default();
+            status.last_attempt = Some(now);
+            match result {
+                Ok(()) => {
+                    status.last_success = Some(now);
+                    status.last_error = None;
+                }
+                Err(err) => status.last_error = Some(err.to_string()),
+            }
+        }
+        drop(statuses);
+
+        // Publish only families of the collected metrics, under the lock as other intervals may be collected meanwhile
+        let mut metric_families = self.metric_families.write().unwrap();
+        *metric_families = merge_metric_families(
+            std::mem::take(&mut *metric_families),
+            self.metrics_collector.registry().gather(),
+            &metrics,
+        );
+        drop(metric_families);
+        self.collected.send_replace(());
+    }
+
+    /// Wait until every enabled collector has been collected at least once, successfully or not.
+    pub(crate) async fn wait_collected(&self) {
+        let all_collected = || {
+            let statuses = self.statuses.read().unwrap();
+            self.schedule
+                .iter()
+                .flat_map(|(_, metrics)| metrics)
+                .all(|metric| statuses.contains_key(metric))
+        };
+        let mut collected = self.collected.subscribe();
+        // Sender lives as long as self, so this never fails
+        let _ = collected.wait_for(|_| all_collected()).await;
+    }
+
+    /// Returns metric families gathered by the last collection.
+    pub(crate) fn metric_families(&self) -> Vec<MetricFamily> {
+        self.metric_families.read().unwrap().clone()
+    }
+
+    /// Returns enabled metrics with their collection interval.
+    pub(crate) fn schedule(&self) -> &[(Duration, Vec<Metrics>)] {
+        &self.schedule
+    }
+
+    /// Returns status of collectors collected at least once.
+    pub(crate) fn statuses(&self) -> HashMap<Metrics, CollectorStatus> {
+        self.statuses.read().unwrap().clone()
+    }
+
+    pub(crate) fn metrics_collector(&self) -> &OpMetricsCollector {
+        &self.metrics_collector
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+
+    use super::*;
+    use crate::testing::metrics_collector;
+
+    #[rstest]
+    fn test_schedule(metrics_collector: OpMetricsCollector) {
+        // Arrange
+        let intervals = HashMap::from([
+            (Metrics::Item, Duration::from_secs(21600)),
+            (Metrics::Document, Duration::from_secs(21600)),
+            (Metrics::ServiceAccount, Duration::from_secs(30)),
+        ]);
+
+        // Act
+        let scheduler = Scheduler::new(
+            metrics_collector,
+            vec![
+                Metrics::Account,
+                Metrics::Item,
+                Metrics::ServiceAccount,
+                Metrics::Document,
+                Metrics::User,
+            ],
+            Duration::from_secs(60),
+            &intervals,
+        );
+
+        // Assert
+        assert_eq!(
+            scheduler.schedule,
+            vec![
+                (
+                    Duration::from_secs(60),
+                    vec![Metrics::Account, Metrics::User]
+                ),
+                (
+                    Duration::from_secs(21600),
+                    vec![Metrics::Item, Metrics::Document]
+                ),
+                (Duration::from_secs(30), vec![Metrics::ServiceAccount]),
+            ]
+        );
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_collect(metrics_collector: OpMetricsCollector) {
+        // Arrange
+        let scheduler = Scheduler::new(
+            metrics_collector,
+            vec![Metrics::Group],
+            Duration::from_secs(60),
+            &HashMap::new(),
+        );
+        assert!(scheduler.metric_families().is_empty());
+
+        // Act
+        scheduler.collect(vec![Metrics::Group]).await;
+
+        // Assert
+        let metric_families = scheduler.metric_families();
+        assert!(metric_families
+            .iter()
+            .any(|mf| mf.name() == "op_group_count_total"));
+        assert!(metric_families
+            .iter()
+            .any(|mf| mf.name() == "op_exporter_last_collection_timestamp_seconds"));
+
+        let statuses = scheduler.statuses();
+        assert!(statuses[&Metrics::Group].last_success.is_some());
+        assert!(statuses[&Metrics::Group].last_error.is_none());
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_wait_collected(metrics_collector: OpMetricsCollector) {
+        // Arrange
+        let scheduler = Arc::new(Scheduler::new(
+            metrics_collector,
+            vec![Metrics::Group, Metrics::Vault],
+            Duration::from_secs(60),
+            &HashMap::from([(Metrics::Vault, Duration::from_secs(3600))]),
+        ));
+        scheduler.collect(vec![Metrics::Group]).await;
+        let waiting = tokio::time::timeout(Duration::from_millis(100), scheduler.wait_collected());
+        assert!(waiting.await.is_err());
+
+        // Act
+        let tasks = scheduler.start();
+        let collected = tokio::time::timeout(Duration::from_secs(5), scheduler.wait_collected());
+
+        // Assert
+        assert!(collected.await.is_ok());
+        tasks.iter().for_each(JoinHandle::abort);
+    }
+}
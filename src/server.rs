@@ -1,103 +1,618 @@
-use std::net::SocketAddr;
+use std::{collections::HashMap,
+          io::Write,
+          net::SocketAddr,
+          str::FromStr,
+          sync::{Arc, RwLock},
+          time::Duration};
 
 use bytes::Bytes;
+use flate2::{write::GzEncoder, Compression};
 use http_body_util::Full;
-use hyper::{header::CONTENT_TYPE, server::conn::http1, service::service_fn, Request, Response};
+use hyper::{header::{HeaderMap, HeaderValue, ACCEPT, ACCEPT_ENCODING, AUTHORIZATION,
+                     CONTENT_ENCODING, CONTENT_TYPE, VARY, WWW_AUTHENTICATE},
+            server::conn::http1,
+            service::service_fn,
+            Request, Response};
 use hyper_util::rt::{TokioIo, TokioTimer};
 use prometheus::{Encoder, TextEncoder};
-use tokio::net::TcpListener;
+use tokio::{io::{AsyncRead, AsyncWrite},
+            net::TcpListener,
+            signal::unix::{signal, SignalKind},
+            sync::watch,
+            task::{JoinHandle, JoinSet}};
+use tokio_rustls::TlsAcceptor;
 
-use crate::{command_executor::OpCommandExecutor,
-            metrics_collector::{Metrics, OpMetricsCollector}};
+use crate::{health, landing_page,
+            metrics_collector::{filter_metric_families, Metrics},
+            openmetrics,
+            scheduler::Scheduler,
+            web_config::WebConfig};
+
+/// State shared by all connections.
+pub(crate) struct ServerState {
+    /// Scheduler of the default instance served at `/metrics`, `None` if only targets are collected.
+    pub(crate) scheduler: Option<Arc<Scheduler>>,
+    /// Schedulers of named targets served at `/probe`.
+    pub(crate) targets: HashMap<String, Arc<Scheduler>>,
+    /// Path to 1Password CLI binary, shown on the landing page.
+    pub(crate) op_path: String,
+    /// Number of collection intervals without success after which not ready.
+    pub(crate) readiness_stale_intervals: u32,
+    /// TLS and basic authentication settings.
+    pub(crate) web_config: WebConfig,
+}
+
+impl ServerState {
+    /// Continue counters of the state being replaced, for the default instance and targets kept.
+    fn inherit_counters(&self, previous: &ServerState) {
+        if let (Some(scheduler), Some(previous)) = (&self.scheduler, &previous.scheduler) {
+            scheduler
+                .metrics_collector()
+                .inherit_counters(previous.metrics_collector());
+        }
+        for (name, scheduler) in &self.targets {
+            if let Some(previous) = previous.targets.get(name) {
+                scheduler
+                    .metrics_collector()
+                    .inherit_counters(previous.metrics_collector());
+            }
+        }
+    }
+}
+
+fn json_response(status: u16, body: &impl serde::Serialize) -> Response<Full<Bytes>> {
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Full::new(Bytes::from(serde_json::to_vec(body).unwrap())))
+        .unwrap()
+}
+
+/// Parse collectors requested by `collect[]` query parameters, allowing only enabled ones.
+///
+/// Returns `None` if no collector requested.
+fn parse_collect_params(
+    query: Option<&str>,
+    enabled: &[Metrics],
+) -> Result<Option<Vec<Metrics>>, String> {
+    let mut metrics = vec![];
+    for (key, value) in form_urlencoded::parse(query.unwrap_or_default().as_bytes()) {
+        if key != "collect[]" {
+            continue;
+        }
+        let metric =
+            Metrics::from_str(&value).map_err(|_| format!("unknown collector: {value}"))?;
+        if !enabled.contains(&metric) {
+            return Err(format!("collector not enabled: {value}"));
+        }
+        metrics.push(metric);
+    }
+
+    Ok((!metrics.is_empty()).then_some(metrics))
+}
+
+fn text_response(status: u16, body: impl Into<Bytes>) -> Response<Full<Bytes>> {
+    Response::builder()
+        .status(status)
+        .body(Full::new(body.into()))
+        .unwrap()
+}
+
+/// Response to requests for the default instance when only targets are collected.
+fn no_default_instance() -> Response<Full<Bytes>> {
+    text_response(404, "Only targets are collected, see /probe?target=<name>")
+}
+
+/// Parse quality values of a header listing media ranges or codings, e.g. `gzip;q=0.5, identity`.
+fn parse_quality_values(header: Option<&HeaderValue>) -> Vec<(String, f32)> {
+    let header = header.and_then(|h| h.to_str().ok()).unwrap_or_default();
+    header
+        .split(',')
+        .filter_map(|item| {
+            let mut params = item.split(';').map(str::trim);
+            let value = params
+                .next()
+                .filter(|v| !v.is_empty())?
+                .to_ascii_lowercase();
+            let quality = match params.find_map(|p| p.strip_prefix("q=")) {
+                Some(q) => q.parse().ok()?,
+                None => 1.0,
+            };
+            Some((value, quality))
+        })
+        .collect()
+}
+
+/// Whether OpenMetrics is preferred over the Prometheus text format by the `Accept` header.
+fn prefers_openmetrics(headers: &HeaderMap) -> bool {
+    let accept = parse_quality_values(headers.get(ACCEPT));
+    let best = |media_types: &[&str]| {
+        accept
+            .iter()
+            .filter(|(value, _)| media_types.contains(&value.as_str()))
+            .map(|(_, quality)| *quality)
+            .fold(0.0, f32::max)
+    };
+    let openmetrics = best(&["application/openmetrics-text"]);
+
+    openmetrics > 0.0 && openmetrics >= best(&["text/plain", "*/*"])
+}
+
+fn accepts_gzip(headers: &HeaderMap) -> bool {
+    parse_quality_values(headers.get(ACCEPT_ENCODING))
+        .iter()
+        .any(|(value, quality)| value == "gzip" && *quality > 0.0)
+}
 
 async fn serve_metrics(
-    metrics: Vec<Metrics>,
-    op_path: String,
-    service_account_token: Option<String>,
+    scheduler: &Scheduler,
+    query: Option<&str>,
+    headers: &HeaderMap,
 ) -> Result<Response<Full<Bytes>>, hyper::Error> {
-    // Collect all metrics
-    let command_executor = OpCommandExecutor::new(op_path, service_account_token);
-    let metrics_collector = OpMetricsCollector::new(Box::new(command_executor));
-    metrics_collector.collect(metrics);
-
-    // Encode to Prometheus format
-    let metric_families = prometheus::gather();
-    let mut buffer = vec![];
-    let encoder = TextEncoder::new();
-    encoder.encode(&metric_families, &mut buffer).unwrap();
-
-    // Respond with the metrics
+    let enabled: Vec<Metrics> = scheduler
+        .schedule()
+        .iter()
+        .flat_map(|(_, metrics)| metrics.clone())
+        .collect();
+    let collect = match parse_collect_params(query, &enabled) {
+        Ok(collect) => collect,
+        Err(err) => return Ok(text_response(400, err)),
+    };
+
+    // Encode last collected metrics in the format client prefers
+    let mut metric_families = scheduler.metric_families();
+    if let Some(collect) = collect {
+        metric_families = filter_metric_families(metric_families, &collect);
+    }
+    let (mut buffer, content_type) = if prefers_openmetrics(headers) {
+        let text = openmetrics::encode(&metric_families);
+        (text.into_bytes(), openmetrics::CONTENT_TYPE)
+    } else {
+        let mut buffer = vec![];
+        let encoder = TextEncoder::new();
+        encoder.encode(&metric_families, &mut buffer).unwrap();
+        (buffer, prometheus::TEXT_FORMAT)
+    };
+
+    // Respond with the metrics, compressed if possible as per-tag metrics can be large
+    let mut response = Response::builder()
+        .status(200)
+        .header(CONTENT_TYPE, content_type)
+        .header(VARY, "Accept, Accept-Encoding");
+    if accepts_gzip(headers) {
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder.write_all(&buffer).unwrap();
+        buffer = encoder.finish().unwrap();
+        response = response.header(CONTENT_ENCODING, "gzip");
+    }
+
+    Ok(response.body(Full::new(Bytes::from(buffer))).unwrap())
+}
+
+/// Serve metrics of the last background collection of a target, rather than probing it on demand.
+async fn serve_probe(
+    state: Arc<ServerState>,
+    query: Option<&str>,
+    headers: &HeaderMap,
+) -> Result<Response<Full<Bytes>>, hyper::Error> {
+    let target = form_urlencoded::parse(query.unwrap_or_default().as_bytes())
+        .find(|(key, _)| key == "target")
+        .map(|(_, value)| value.into_owned());
+    let Some(target) = target else {
+        return Ok(text_response(400, "Missing target parameter"));
+    };
+    let Some(scheduler) = state.targets.get(&target) else {
+        return Ok(text_response(404, format!("Unknown target: {target}")));
+    };
+
+    serve_metrics(scheduler, query, headers).await
+}
+
+async fn serve_snapshot(
+    state: Arc<ServerState>,
+    query: Option<&str>,
+) -> Result<Response<Full<Bytes>>, hyper::Error> {
+    // Snapshot of the default instance unless a target given
+    let target = form_urlencoded::parse(query.unwrap_or_default().as_bytes())
+        .find(|(key, _)| key == "target")
+        .map(|(_, value)| value.into_owned());
+    let scheduler = match &target {
+        Some(target) => match state.targets.get(target) {
+            Some(scheduler) => scheduler,
+            None => return Ok(text_response(404, format!("Unknown target: {target}"))),
+        },
+        None => match &state.scheduler {
+            Some(scheduler) => scheduler,
+            None => return Ok(no_default_instance()),
+        },
+    };
+
+    Ok(json_response(
+        200,
+        &scheduler.metrics_collector().snapshot(),
+    ))
+}
+
+async fn serve_landing_page(
+    state: Arc<ServerState>,
+) -> Result<Response<Full<Bytes>>, hyper::Error> {
+    let page =
+        landing_page::render(state.scheduler.as_deref(), &state.op_path, &state.targets).await;
     let response = Response::builder()
         .status(200)
-        .header(CONTENT_TYPE, encoder.format_type())
-        .body(Full::new(Bytes::from(buffer)))
+        .header(CONTENT_TYPE, "text/html; charset=utf-8")
+        .body(Full::new(Bytes::from(page)))
         .unwrap();
 
     Ok(response)
 }
 
+async fn serve_healthz() -> Result<Response<Full<Bytes>>, hyper::Error> {
+    // Liveness only, the process is able to serve requests
+    Ok(json_response(200, &serde_json::json!({ "status": "ok" })))
+}
+
+async fn serve_readyz(state: Arc<ServerState>) -> Result<Response<Full<Bytes>>, hyper::Error> {
+    let readiness = health::check_server_readiness(
+        state.scheduler.as_deref(),
+        &state.targets,
+        state.readiness_stale_intervals,
+    )
+    .await;
+    let status = if readiness.is_ready() { 200 } else { 503 };
+
+    Ok(json_response(status, &readiness))
+}
+
 async fn serve(
     req: Request<impl hyper::body::Body>,
-    metrics: Vec<Metrics>,
-    op_path: String,
-    service_account_token: Option<String>,
+    state: Arc<ServerState>,
 ) -> Result<Response<Full<Bytes>>, hyper::Error> {
+    let authorization = req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok());
+    if !state.web_config.authorize(authorization).await {
+        let response = Response::builder()
+            .status(401)
+            .header(WWW_AUTHENTICATE, "Basic")
+            .body(Full::new(Bytes::from("Unauthorized")))
+            .unwrap();
+        return Ok(response);
+    }
+
     match (req.method(), req.uri().path()) {
-        (&hyper::Method::GET, "/metrics") => {
-            serve_metrics(metrics, op_path, service_account_token).await
+        (&hyper::Method::GET, "/") => serve_landing_page(state).await,
+        (&hyper::Method::GET, "/metrics") => match &state.scheduler {
+            Some(scheduler) => serve_metrics(scheduler, req.uri().query(), req.headers()).await,
+            None => Ok(no_default_instance()),
+        },
+        (&hyper::Method::GET, "/probe") => {
+            serve_probe(state, req.uri().query(), req.headers()).await
         }
-        _ => {
-            let response = Response::builder()
-                .status(404)
-                .body(Full::new(Bytes::from("Not Found")))
-                .unwrap();
-            Ok(response)
+        (&hyper::Method::GET, "/api/v1/snapshot") => serve_snapshot(state, req.uri().query()).await,
+        (&hyper::Method::GET, "/healthz") => serve_healthz().await,
+        (&hyper::Method::GET, "/readyz") => serve_readyz(state).await,
+        _ => Ok(text_response(404, "Not Found")),
+    }
+}
+
+/// State currently served, replaced on reload.
+type SharedState = Arc<RwLock<Arc<ServerState>>>;
+
+/// Server state in effect, with its background collection tasks.
+struct Running {
+    state: Arc<ServerState>,
+    tls_acceptor: Option<TlsAcceptor>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl Running {
+    fn start(state: ServerState) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let mut running = Self::new(state)?;
+        running.spawn_collections();
+
+        Ok(running)
+    }
+
+    fn new(state: ServerState) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let tls_acceptor = state.web_config.tls_config()?.map(TlsAcceptor::from);
+
+        Ok(Running {
+            state: Arc::new(state),
+            tls_acceptor,
+            tasks: vec![],
+        })
+    }
+
+    /// Collect metrics in background, scrapes only read the last snapshot.
+    fn spawn_collections(&mut self) {
+        for scheduler in self
+            .state
+            .scheduler
+            .iter()
+            .chain(self.state.targets.values())
+        {
+            self.tasks.extend(scheduler.start());
         }
     }
+
+    /// Start reloaded `state` in place of this one, which keeps serving its last snapshot without collecting.
+    ///
+    /// Collections of this one are stopped before its counters are inherited, so none counted afterwards are lost and
+    /// the CLI is not run by both meanwhile.
+    fn supersede(
+        &self,
+        state: ServerState,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let mut reloaded = Self::new(state)?;
+        self.stop();
+        reloaded.state.inherit_counters(&self.state);
+        reloaded.spawn_collections();
+
+        Ok(reloaded)
+    }
+
+    /// Wait until the default instance and all targets have been collected once.
+    async fn wait_collected(&self) {
+        let schedulers = self
+            .state
+            .scheduler
+            .iter()
+            .chain(self.state.targets.values());
+        futures::future::join_all(schedulers.map(|s| s.wait_collected())).await;
+    }
+
+    /// Stop background collections. Running 1Password CLI processes are killed as their futures dropped.
+    fn stop(&self) {
+        self.tasks.iter().for_each(JoinHandle::abort);
+    }
 }
 
+/// Serve until SIGTERM or SIGINT, reloading state with `reload` on SIGHUP.
 pub(crate) async fn run_server(
-    host: String,
+    host: &str,
     port: u16,
-    metrics: Vec<Metrics>,
-    op_path: String,
-    service_account_token: Option<String>,
+    state: ServerState,
+    shutdown_timeout: Duration,
+    reload: impl Fn() -> Result<ServerState, Box<dyn std::error::Error + Send + Sync>>,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let addr: SocketAddr = format!("{host}:{port}").parse()?;
 
-    log::info!("Enabled metrics: {:?}", metrics);
-    log::info!("Using 1Password CLI: {}", op_path);
-    if service_account_token.is_some() {
-        log::warn!("Service account token explicitly set.");
-    }
+    let mut running = Running::start(state)?;
+    let shared_state: SharedState = Arc::new(RwLock::new(running.state.clone()));
+    // Reloaded state collecting for the first time, served once collected
+    let mut pending: Option<Running> = None;
+
+    let mut terminate = signal(SignalKind::terminate())?;
+    let mut hangup = signal(SignalKind::hangup())?;
+    let (shutdown_tx, shutdown_rx) = watch::channel(());
+    let mut connections = JoinSet::new();
+
     let listener = TcpListener::bind(addr).await?;
-    log::info!("Listening on http://{}", listener.local_addr()?);
+    let scheme = if running.tls_acceptor.is_some() {
+        "https"
+    } else {
+        "http"
+    };
+    log::info!("Listening on {}://{}", scheme, listener.local_addr()?);
     loop {
-        let (tcp, _) = listener.accept().await?;
-        let io = TokioIo::new(tcp);
-
-        let metrics = metrics.clone();
-        let op_path = op_path.clone();
-        let service_account_token = service_account_token.clone();
-
-        tokio::task::spawn(async move {
-            if let Err(err) = http1::Builder::new()
-                .timer(TokioTimer::new())
-                .serve_connection(
-                    io,
-                    service_fn(move |req| {
-                        serve(
-                            req,
-                            metrics.clone(),
-                            op_path.clone(),
-                            service_account_token.clone(),
-                        )
-                    }),
-                )
-                .await
-            {
-                log::error!("Error serving connection: {:?}", err);
+        tokio::select! {
+            accepted = listener.accept() => {
+                let (tcp, _) = accepted?;
+                while connections.try_join_next().is_some() {}
+
+                let state = shared_state.clone();
+                let tls_acceptor = running.tls_acceptor.clone();
+                let shutdown_rx = shutdown_rx.clone();
+
+                connections.spawn(async move {
+                    match tls_acceptor {
+                        Some(tls_acceptor) => match tls_acceptor.accept(tcp).await {
+                            Ok(tls) => serve_connection(tls, state, shutdown_rx).await,
+                            Err(err) => log::debug!("TLS handshake failed: {:?}", err),
+                        },
+                        None => serve_connection(tcp, state, shutdown_rx).await,
+                    }
+                });
+            }
+            _ = hangup.recv() => {
+                log::info!("Received SIGHUP, reloading configuration");
+                // A reload still collecting for the first time is replaced as well, carrying its counters over
+                let reloaded = reload()
+                    .and_then(|state| pending.as_ref().unwrap_or(&running).supersede(state));
+                match reloaded {
+                    Ok(reloaded) => {
+                        log::info!("Configuration reloaded, serving it after its first collection");
+                        pending = Some(reloaded);
+                    }
+                    Err(err) => log::error!("Failed to reload configuration, keep the current one: {}", err),
+                }
             }
-        });
+            _ = async { pending.as_ref().unwrap().wait_collected().await }, if pending.is_some() => {
+                running.stop();
+                running = pending.take().unwrap();
+                *shared_state.write().unwrap() = running.state.clone();
+                log::info!("Serving reloaded configuration");
+            }
+            _ = terminate.recv() => break,
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+
+    log::info!(
+        "Shutting down, waiting up to {} for {} connections",
+        humantime::format_duration(shutdown_timeout),
+        connections.len()
+    );
+    drop(listener);
+    running.stop();
+    if let Some(pending) = &pending {
+        pending.stop();
+    }
+    shutdown_tx.send_replace(());
+    let drained = tokio::time::timeout(shutdown_timeout, async {
+        while connections.join_next().await.is_some() {}
+    })
+    .await;
+    if drained.is_err() {
+        log::warn!(
+            "Closing {} connections not finished in time",
+            connections.len()
+        );
+        connections.shutdown().await;
+    }
+
+    Ok(())
+}
+
+/// Serve HTTP on a connection until closed or shutdown requested, letting the in-flight request finish.
+async fn serve_connection(
+    io: impl AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    state: SharedState,
+    mut shutdown_rx: watch::Receiver<()>,
+) {
+    let conn = http1::Builder::new()
+        .timer(TokioTimer::new())
+        .serve_connection(
+            TokioIo::new(io),
+            service_fn(move |req| {
+                let state = state.read().unwrap().clone();
+                serve(req, state)
+            }),
+        );
+    tokio::pin!(conn);
+
+    let result = tokio::select! {
+        result = conn.as_mut() => result,
+        _ = shutdown_rx.changed() => {
+            conn.as_mut().graceful_shutdown();
+            conn.await
+        }
+    };
+    if let Err(err) = result {
+        log::error!("Error serving connection: {:?}", err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mockall::predicate::*;
+
+    use super::*;
+    use crate::{command_executor::{MockCommandExecutor, OpError},
+                metrics_collector::OpMetricsCollector};
+
+    /// State collecting groups every 50ms, failing each time.
+    fn failing_state() -> ServerState {
+        let mut command_executor = MockCommandExecutor::new();
+        command_executor
+            .expect_exec()
+            .with(eq(vec!["group", "list", "--format", "json"]))
+            .returning(|_| Err(OpError::Timeout(Duration::from_secs(1))));
+        let scheduler = Scheduler::new(
+            OpMetricsCollector::new(Box::new(command_executor)),
+            vec![Metrics::Group],
+            Duration::from_millis(50),
+            &HashMap::new(),
+        );
+
+        ServerState {
+            scheduler: Some(Arc::new(scheduler)),
+            targets: HashMap::new(),
+            op_path: "op".to_string(),
+            readiness_stale_intervals: 3,
+            web_config: WebConfig::default(),
+        }
+    }
+
+    /// Value of `op_exporter_collector_errors_total` served by the default instance.
+    fn errors_total(running: &Running) -> f64 {
+        running
+            .state
+            .scheduler
+            .as_ref()
+            .unwrap()
+            .metric_families()
+            .iter()
+            .filter(|mf| mf.name() == "op_exporter_collector_errors_total")
+            .flat_map(|mf| mf.get_metric())
+            .map(|m| m.get_counter().value())
+            .sum()
+    }
+
+    #[tokio::test]
+    async fn test_supersede_keeps_counters_monotonic() {
+        // Arrange
+        let running = Running::start(failing_state()).unwrap();
+        running.wait_collected().await;
+        tokio::time::sleep(Duration::from_millis(120)).await;
+        let before = errors_total(&running);
+
+        // Act
+        let reloaded = running.supersede(failing_state()).unwrap();
+        reloaded.wait_collected().await;
+        tokio::time::sleep(Duration::from_millis(120)).await;
+
+        // Assert
+        assert!(before >= 1.0);
+        // Superseded state stopped collecting, the reloaded one continues from its count
+        assert_eq!(errors_total(&running), before);
+        assert!(errors_total(&reloaded) > before);
+
+        reloaded.stop();
+    }
+
+    #[test]
+    fn test_prefers_openmetrics() {
+        let headers = |accept: &str| HeaderMap::from_iter([(ACCEPT, accept.parse().unwrap())]);
+
+        // Sent by Prometheus
+        assert!(prefers_openmetrics(&headers(
+            "application/openmetrics-text;version=1.0.0;q=0.5,application/openmetrics-text;version=0.0.1;q=0.4,text/plain;version=0.0.4;q=0.3,*/*;q=0.2"
+        )));
+        assert!(prefers_openmetrics(&headers(
+            "application/openmetrics-text"
+        )));
+        assert!(!prefers_openmetrics(&headers(
+            "text/plain;version=0.0.4,application/openmetrics-text;q=0.5"
+        )));
+        assert!(!prefers_openmetrics(&headers("*/*")));
+        assert!(!prefers_openmetrics(&HeaderMap::new()));
+    }
+
+    #[test]
+    fn test_accepts_gzip() {
+        let headers =
+            |encoding: &str| HeaderMap::from_iter([(ACCEPT_ENCODING, encoding.parse().unwrap())]);
+
+        assert!(accepts_gzip(&headers("gzip")));
+        assert!(accepts_gzip(&headers("deflate, gzip;q=0.5")));
+        assert!(!accepts_gzip(&headers("gzip;q=0")));
+        assert!(!accepts_gzip(&headers("identity")));
+        assert!(!accepts_gzip(&HeaderMap::new()));
+    }
+
+    #[test]
+    fn test_parse_collect_params() {
+        let enabled = [Metrics::ServiceAccount, Metrics::Item];
+
+        assert_eq!(parse_collect_params(None, &enabled), Ok(None));
+        assert_eq!(parse_collect_params(Some("foo=bar"), &enabled), Ok(None));
+        assert_eq!(
+            parse_collect_params(
+                Some("collect[]=service-account&collect%5B%5D=item"),
+                &enabled
+            ),
+            Ok(Some(vec![Metrics::ServiceAccount, Metrics::Item]))
+        );
+        assert_eq!(
+            parse_collect_params(Some("collect[]=vault"), &enabled),
+            Err("collector not enabled: vault".to_string())
+        );
+        assert_eq!(
+            parse_collect_params(Some("collect[]=unknown"), &enabled),
+            Err("unknown collector: unknown".to_string())
+        );
     }
 }
@@ -51,6 +51,18 @@ pub(crate) fn command_executor() -> MockCommandExecutor {
             .with(eq(args))
             .returning(move |_| Ok(output.to_string()));
     }
+    for (args, output) in [
+        (vec!["--version"], "2.30.0\n"),
+        (
+            vec!["whoami", "--format", "json"],
+            include_str!(test_dir!("fixtures/whoami.json")),
+        ),
+    ] {
+        command_executor
+            .expect_exec_unqueued()
+            .with(eq(args))
+            .returning(move |_| Ok(output.to_string()));
+    }
 
     command_executor
 }
@@ -0,0 +1,524 @@
+use std::{collections::{BTreeMap, HashMap, HashSet},
+          path::{Path, PathBuf},
+          sync::{Arc, RwLock},
+          time::SystemTime};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use ring::digest::{digest, SHA256};
+use rustls::{pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer},
+             server::{ClientHello, ResolvesServerCert, WebPkiClientVerifier},
+             sign::CertifiedKey,
+             version::{TLS12, TLS13},
+             RootCertStore, ServerConfig, SupportedProtocolVersion};
+use serde::Deserialize;
+
+use crate::config::ConfigError;
+
+/// Web configuration, a subset of the Prometheus exporter-toolkit web configuration file.
+///
+/// Other keys of exporter-toolkit, such as `http_server_config`, are accepted and ignored with a warning, so a file
+/// shared with other exporters can be used.
+#[derive(Deserialize, Debug, Default)]
+pub(crate) struct WebConfig {
+    tls_server_config: Option<TlsServerConfig>,
+    /// Users allowed to access the exporter, mapped to their bcrypt password hash.
+    #[serde(default)]
+    basic_auth_users: HashMap<String, String>,
+    #[serde(flatten)]
+    unsupported: BTreeMap<String, serde_yaml_ng::Value>,
+    /// Hash verified against for unknown users, so they take as long as known ones to reject.
+    #[serde(skip)]
+    dummy_hash: String,
+    /// Digests of credentials verified already, to not pay bcrypt cost on every request.
+    #[serde(skip)]
+    verified: RwLock<HashSet<Vec<u8>>>,
+}
+
+#[derive(Deserialize, Debug)]
+struct TlsServerConfig {
+    cert_file: PathBuf,
+    key_file: PathBuf,
+    #[serde(default)]
+    client_auth_type: ClientAuthType,
+    /// CA certificates to verify client certificates against.
+    client_ca_file: Option<PathBuf>,
+    #[serde(default)]
+    min_version: TlsVersion,
+    #[serde(flatten)]
+    unsupported: BTreeMap<String, serde_yaml_ng::Value>,
+}
+
+/// Policy of client certificates, named as in exporter-toolkit.
+#[derive(Deserialize, Debug, Default, PartialEq)]
+enum ClientAuthType {
+    #[default]
+    NoClientCert,
+    VerifyClientCertIfGiven,
+    RequireAndVerifyClientCert,
+    /// Accepting client certificates without verifying them, which is not supported.
+    #[serde(other)]
+    Unverified,
+}
+
+#[derive(Deserialize, Debug, Default, PartialEq)]
+enum TlsVersion {
+    /// Older versions are not supported, so the same as TLS 1.2.
+    #[serde(alias = "TLS10", alias = "TLS11")]
+    #[default]
+    TLS12,
+    TLS13,
+}
+
+impl WebConfig {
+    /// Load web configuration from YAML file. Relative paths in it are resolved against the file's directory.
+    pub(crate) fn load(path: &Path) -> Result<Self, ConfigError> {
+        let content = std::fs::read_to_string(path)?;
+        let mut web_config = Self::parse(&content)?;
+        if let Some(tls) = &mut web_config.tls_server_config {
+            let dir = path.parent().unwrap_or(Path::new(""));
+            tls.cert_file = dir.join(&tls.cert_file);
+            tls.key_file = dir.join(&tls.key_file);
+            tls.client_ca_file = tls.client_ca_file.as_ref().map(|ca| dir.join(ca));
+        }
+
+        Ok(web_config)
+    }
+
+    fn parse(content: &str) -> Result<Self, ConfigError> {
+        if content.trim().is_empty() {
+            return Ok(WebConfig::default());
+        }
+        let mut web_config: WebConfig = serde_yaml_ng::from_str(content)?;
+        let invalid = |key: &str, message: &str| {
+            Err(ConfigError::Invalid {
+                key: key.to_string(),
+                message: message.to_string(),
+            })
+        };
+        let mut unsupported: Vec<String> = web_config.unsupported.keys().cloned().collect();
+        if let Some(tls) = &web_config.tls_server_config {
+            match tls.client_auth_type {
+                ClientAuthType::Unverified => {
+                    return invalid(
+                        "tls_server_config.client_auth_type",
+                        "is not supported, use `VerifyClientCertIfGiven` or `RequireAndVerifyClientCert`",
+                    )
+                }
+                ClientAuthType::NoClientCert => {}
+                _ if tls.client_ca_file.is_none() => {
+                    return invalid(
+                        "tls_server_config.client_ca_file",
+                        "is required to verify client certificates",
+                    )
+                }
+                _ => {}
+            }
+            unsupported.extend(
+                tls.unsupported
+                    .keys()
+                    .map(|key| format!("tls_server_config.{key}")),
+            );
+        }
+        for key in unsupported {
+            log::warn!("Ignoring unsupported web configuration key: {}", key);
+        }
+        for (user, hash) in &web_config.basic_auth_users {
+            // Verifying against malformed hash fails regardless of the password
+            if bcrypt::verify("", hash).is_err() {
+                return Err(ConfigError::Invalid {
+                    key: format!("basic_auth_users.{user}"),
+                    message: "is not a valid bcrypt hash".to_string(),
+                });
+            }
+        }
+        web_config.dummy_hash = web_config.dummy_hash();
+
+        Ok(web_config)
+    }
+
+    /// Returns TLS configuration of the server, if enabled. Certificate and key are reloaded when changed.
+    pub(crate) fn tls_config(&self) -> Result<Option<Arc<ServerConfig>>, String> {
+        let Some(tls) = &self.tls_server_config else {
+            return Ok(None);
+        };
+        let resolver = CertResolver {
+            cert_file: tls.cert_file.clone(),
+            key_file: tls.key_file.clone(),
+            cached: RwLock::new(None),
+        };
+        // Fail early on startup rather than on first connection
+        resolver.load()?;
+
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let versions: &[&SupportedProtocolVersion] = match tls.min_version {
+            TlsVersion::TLS12 => &[&TLS13, &TLS12],
+            TlsVersion::TLS13 => &[&TLS13],
+        };
+        let builder = ServerConfig::builder_with_provider(provider.clone())
+            .with_protocol_versions(versions)
+            .map_err(|err| err.to_string())?;
+        let builder = match &tls.client_ca_file {
+            Some(ca_file) if tls.client_auth_type != ClientAuthType::NoClientCert => {
+                let mut roots = RootCertStore::empty();
+                for cert in CertificateDer::pem_file_iter(ca_file)
+                    .map_err(|err| format!("{}: {err}", ca_file.display()))?
+                {
+                    roots
+                        .add(cert.map_err(|err| format!("{}: {err}", ca_file.display()))?)
+                        .map_err(|err| format!("{}: {err}", ca_file.display()))?;
+                }
+                let mut verifier =
+                    WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider);
+                if tls.client_auth_type == ClientAuthType::VerifyClientCertIfGiven {
+                    verifier = verifier.allow_unauthenticated();
+                }
+                builder.with_client_cert_verifier(verifier.build().map_err(|err| err.to_string())?)
+            }
+            _ => builder.with_no_client_auth(),
+        };
+        let mut config = builder.with_cert_resolver(Arc::new(resolver));
+        config.alpn_protocols = vec![b"http/1.1".to_vec()];
+
+        Ok(Some(Arc::new(config)))
+    }
+
+    /// Hash of the same cost as the configured ones, empty if no user configured.
+    fn dummy_hash(&self) -> String {
+        self.basic_auth_users
+            .values()
+            .filter_map(|hash| hash.parse::<bcrypt::HashParts>().ok())
+            .map(|parts| parts.get_cost())
+            .max()
+            .and_then(|cost| bcrypt::hash("", cost).ok())
+            .unwrap_or_default()
+    }
+
+    /// Check the `Authorization` header value against the configured users. Always passes if no user configured.
+    pub(crate) async fn authorize(&self, authorization: Option<&str>) -> bool {
+        if self.basic_auth_users.is_empty() {
+            return true;
+        }
+        let credentials = authorization
+            .and_then(|value| value.strip_prefix("Basic "))
+            .and_then(|encoded| STANDARD.decode(encoded).ok())
+            .and_then(|decoded| String::from_utf8(decoded).ok());
+        let Some((user, password)) = credentials.as_deref().and_then(|c| c.split_once(':')) else {
+            return false;
+        };
+        let (known, hash) = match self.basic_auth_users.get(user) {
+            Some(hash) => (true, hash.clone()),
+            None => (false, self.dummy_hash.clone()),
+        };
+
+        // Keyed by the hash too, so a changed password of the user is verified again
+        let key = digest(&SHA256, format!("{user}\0{hash}\0{password}").as_bytes())
+            .as_ref()
+            .to_vec();
+        if known && self.verified.read().unwrap().contains(&key) {
+            return true;
+        }
+
+        // Verifying takes long by design, keep it off the async runtime threads
+        let password = password.to_string();
+        let verified =
+            tokio::task::spawn_blocking(move || bcrypt::verify(password, &hash).unwrap_or(false))
+                .await
+                .unwrap_or(false);
+        if !(known && verified) {
+            return false;
+        }
+        self.verified.write().unwrap().insert(key);
+
+        true
+    }
+}
+
+/// Modification times of the certificate and key files.
+type ModifiedTimes = (SystemTime, SystemTime);
+
+/// Resolves server certificate, reloading it from files when their modification time changes.
+#[derive(Debug)]
+struct CertResolver {
+    cert_file: PathBuf,
+    key_file: PathBuf,
+    /// Last loaded key with modification times of the files it was loaded from.
+    cached: RwLock<Option<(ModifiedTimes, Arc<CertifiedKey>)>>,
+}
+
+impl CertResolver {
+    fn load(&self) -> Result<Arc<CertifiedKey>, String> {
+        let modified = |path: &Path| {
+            std::fs::metadata(path)
+                .and_then(|m| m.modified())
+                .map_err(|err| format!("{}: {err}", path.display()))
+        };
+        let mtimes = (modified(&self.cert_file)?, modified(&self.key_file)?);
+        if let Some((cached_mtimes, key)) = &*self.cached.read().unwrap() {
+            if *cached_mtimes == mtimes {
+                return Ok(key.clone());
+            }
+        }
+
+        let certs = CertificateDer::pem_file_iter(&self.cert_file)
+            .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+            .map_err(|err| format!("{}: {err}", self.cert_file.display()))?;
+        let key = PrivateKeyDer::from_pem_file(&self.key_file)
+            .map_err(|err| format!("{}: {err}", self.key_file.display()))?;
+        let key = rustls::crypto::ring::sign::any_supported_type(&key)
+            .map_err(|err| format!("{}: {err}", self.key_file.display()))?;
+        let key = Arc::new(CertifiedKey::new(certs, key));
+        log::info!("Loaded TLS certificate from {}", self.cert_file.display());
+
+        *self.cached.write().unwrap() = Some((mtimes, key.clone()));
+        Ok(key)
+    }
+}
+
+impl ResolvesServerCert for CertResolver {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        match self.load() {
+            Ok(key) => Some(key),
+            Err(err) => {
+                // Keep serving with the previous certificate while files are being replaced
+                log::error!("Failed to reload TLS certificate: {}", err);
+                self.cached
+                    .read()
+                    .unwrap()
+                    .as_ref()
+                    .map(|(_, key)| key.clone())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let hash = bcrypt::hash("secret", 4).unwrap();
+        let web_config = WebConfig::parse(&format!(
+            r#"
+            tls_server_config:
+              cert_file: server.crt
+              key_file: server.key
+            basic_auth_users:
+              alice: {hash}
+            "#
+        ))
+        .unwrap();
+
+        let tls = web_config.tls_server_config.unwrap();
+        assert_eq!(tls.cert_file, PathBuf::from("server.crt"));
+        assert_eq!(tls.key_file, PathBuf::from("server.key"));
+        assert_eq!(web_config.basic_auth_users["alice"], hash);
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        let err = WebConfig::parse("basic_auth_users:\n  alice: plaintext\n").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid config file: `basic_auth_users.alice` is not a valid bcrypt hash"
+        );
+
+        let err = WebConfig::parse(
+            "tls_server_config:\n  cert_file: server.crt\n  key_file: server.key\n  client_auth_type: RequireAnyClientCert\n",
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid config file: `tls_server_config.client_auth_type` is not supported, use `VerifyClientCertIfGiven` or `RequireAndVerifyClientCert`"
+        );
+
+        let err = WebConfig::parse(
+            "tls_server_config:\n  cert_file: server.crt\n  key_file: server.key\n  client_auth_type: RequireAndVerifyClientCert\n",
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid config file: `tls_server_config.client_ca_file` is required to verify client certificates"
+        );
+    }
+
+    #[test]
+    fn test_parse_exporter_toolkit() {
+        let web_config = WebConfig::parse(
+            r#"
+            tls_server_config:
+              cert_file: server.crt
+              key_file: server.key
+              client_auth_type: RequireAndVerifyClientCert
+              client_ca_file: ca.crt
+              min_version: TLS13
+              cipher_suites: [TLS_AES_128_GCM_SHA256]
+            http_server_config:
+              headers:
+                X-Frame-Options: deny
+            "#,
+        )
+        .unwrap();
+
+        let tls = web_config.tls_server_config.unwrap();
+        assert_eq!(
+            tls.client_auth_type,
+            ClientAuthType::RequireAndVerifyClientCert
+        );
+        assert_eq!(tls.client_ca_file, Some(PathBuf::from("ca.crt")));
+        assert_eq!(tls.min_version, TlsVersion::TLS13);
+        // Ignored with a warning
+        assert!(tls.unsupported.contains_key("cipher_suites"));
+        assert!(web_config.unsupported.contains_key("http_server_config"));
+    }
+
+    #[tokio::test]
+    async fn test_authorize() {
+        let hash = bcrypt::hash("secret", 4).unwrap();
+        let web_config =
+            WebConfig::parse(&format!("basic_auth_users:\n  alice: {hash}\n")).unwrap();
+        let basic = |credentials: &str| format!("Basic {}", STANDARD.encode(credentials));
+
+        assert!(web_config.authorize(Some(&basic("alice:secret"))).await);
+        assert!(!web_config.authorize(Some(&basic("alice:wrong"))).await);
+        assert!(!web_config.authorize(Some(&basic("bob:secret"))).await);
+        assert!(!web_config.authorize(Some("Bearer token")).await);
+        assert!(!web_config.authorize(None).await);
+        // Only the correct credentials are cached
+        assert_eq!(web_config.verified.read().unwrap().len(), 1);
+        assert!(web_config.authorize(Some(&basic("alice:secret"))).await);
+
+        assert!(WebConfig::default().authorize(None).await);
+    }
+
+    #[test]
+    fn test_dummy_hash() {
+        let web_config = WebConfig::parse(&format!(
+            "basic_auth_users:\n  alice: {}\n  bob: {}\n",
+            bcrypt::hash("secret", 4).unwrap(),
+            bcrypt::hash("secret", 5).unwrap(),
+        ))
+        .unwrap();
+
+        let parts: bcrypt::HashParts = web_config.dummy_hash.parse().unwrap();
+        assert_eq!(parts.get_cost(), 5);
+        assert!(WebConfig::default().dummy_hash().is_empty());
+    }
+
+    /// Handshake in memory with a client trusting `ca`, presenting `client_cert` if given and limited to `versions`.
+    fn handshake(
+        server_config: Arc<ServerConfig>,
+        ca: &rcgen::CertifiedKey,
+        client_cert: Option<&rcgen::CertifiedKey>,
+        versions: &[&'static SupportedProtocolVersion],
+    ) -> Result<(), rustls::Error> {
+        let mut roots = RootCertStore::empty();
+        roots.add(ca.cert.der().clone())?;
+        let builder = rustls::ClientConfig::builder_with_provider(Arc::new(
+            rustls::crypto::ring::default_provider(),
+        ))
+        .with_protocol_versions(versions)?
+        .with_root_certificates(roots);
+        let client_config = match client_cert {
+            Some(cert) => builder.with_client_auth_cert(
+                vec![cert.cert.der().clone()],
+                PrivateKeyDer::Pkcs8(cert.key_pair.serialize_der().into()),
+            )?,
+            None => builder.with_no_client_auth(),
+        };
+        let mut client = rustls::ClientConnection::new(
+            Arc::new(client_config),
+            "localhost".try_into().unwrap(),
+        )?;
+        let mut server = rustls::ServerConnection::new(server_config)?;
+
+        // Exchange records until both sides are done, or one of them rejects the other
+        for _ in 0..10 {
+            let mut records = vec![];
+            while client.wants_write() {
+                client.write_tls(&mut records).unwrap();
+            }
+            server.read_tls(&mut records.as_slice()).unwrap();
+            server.process_new_packets()?;
+
+            let mut records = vec![];
+            while server.wants_write() {
+                server.write_tls(&mut records).unwrap();
+            }
+            client.read_tls(&mut records.as_slice()).unwrap();
+            client.process_new_packets()?;
+
+            if !client.is_handshaking() && !server.is_handshaking() {
+                return Ok(());
+            }
+        }
+        panic!("handshake did not complete");
+    }
+
+    #[test]
+    fn test_tls_config_client_auth() -> anyhow::Result<()> {
+        // Arrange
+        let dir = std::env::temp_dir().join(format!("op-exporter-mtls-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let server_cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()])?;
+        let client_cert = rcgen::generate_simple_self_signed(vec!["prometheus".to_string()])?;
+        std::fs::write(dir.join("server.crt"), server_cert.cert.pem())?;
+        std::fs::write(dir.join("server.key"), server_cert.key_pair.serialize_pem())?;
+        std::fs::write(dir.join("ca.crt"), client_cert.cert.pem())?;
+        let web_config_file = dir.join("web.yml");
+        std::fs::write(
+            &web_config_file,
+            "tls_server_config:\n  cert_file: server.crt\n  key_file: server.key\n  client_auth_type: RequireAndVerifyClientCert\n  client_ca_file: ca.crt\n  min_version: TLS13\n",
+        )?;
+
+        // Act
+        let config = WebConfig::load(&web_config_file)?
+            .tls_config()
+            .unwrap()
+            .unwrap();
+
+        // Assert
+        let handshake = |client_cert, versions: &[&'static SupportedProtocolVersion]| {
+            handshake(config.clone(), &server_cert, client_cert, versions)
+        };
+        assert!(handshake(Some(&client_cert), &[&TLS13]).is_ok());
+        assert!(handshake(None, &[&TLS13]).is_err());
+        assert!(handshake(Some(&server_cert), &[&TLS13]).is_err());
+        assert!(handshake(Some(&client_cert), &[&TLS12]).is_err());
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_cert_reload() -> anyhow::Result<()> {
+        // Arrange
+        let dir = std::env::temp_dir().join(format!("op-exporter-tls-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let write_cert = |name: &str| -> anyhow::Result<()> {
+            let cert = rcgen::generate_simple_self_signed(vec![name.to_string()])?;
+            std::fs::write(dir.join("server.crt"), cert.cert.pem())?;
+            std::fs::write(dir.join("server.key"), cert.key_pair.serialize_pem())?;
+            Ok(())
+        };
+        write_cert("localhost")?;
+        let resolver = CertResolver {
+            cert_file: dir.join("server.crt"),
+            key_file: dir.join("server.key"),
+            cached: RwLock::new(None),
+        };
+        let first = resolver.load().unwrap();
+
+        // Act
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        write_cert("example.com")?;
+        let second = resolver.load().unwrap();
+
+        // Assert
+        assert!(Arc::ptr_eq(&second, &resolver.load().unwrap()));
+        assert_ne!(first.cert, second.cert);
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
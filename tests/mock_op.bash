@@ -7,6 +7,10 @@ log_file="${file_dir}/test.log"
 echo "Running mock_op.bash with args: \"$@\"" >> $log_file
 
 case "$@" in
+  "--version")
+    echo "2.30.0"
+    exit 0
+    ;;
   "account get --format json")
     serve_file="${fixture_dir}/account.json"
     ;;
@@ -33,9 +37,16 @@ case "$@" in
     ;;
   *)
     echo "No mock specified for command: \"$@\"" >> $log_file
+    echo "[ERROR] No mock specified for command: \"$@\"" >&2
     exit 255
     ;;
 esac
 
+# Simulate slow command, touching the marker file only if not killed meanwhile
+if [ -n "$MOCK_OP_DELAY" ]; then
+  sleep "$MOCK_OP_DELAY"
+  [ -n "$MOCK_OP_DONE_MARKER" ] && touch "$MOCK_OP_DONE_MARKER"
+fi
+
 echo "Served fixture file $serve_file for command \"$@\"" >> $log_file
 cat $serve_file
@@ -1,5 +1,5 @@
 #![allow(dead_code)]
-/// Test helper utilities for unit and integration tests.
+//! Test helper utilities for unit and integration tests.
 
 pub(crate) const MOCK_OP: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/mock_op.bash");
 
@@ -19,3 +19,23 @@ pub(crate) fn get_random_port() -> u16 {
         .expect("Failed to get local address")
         .port()
 }
+
+/// Metrics whose values change on every collection.
+const VOLATILE_METRICS: &[&str] = &[
+    "op_exporter_collector_duration_seconds",
+    "op_exporter_last_collection_timestamp_seconds",
+];
+
+/// Remove volatile metrics from Prometheus text output, as their values differ on every run.
+pub(crate) fn strip_volatile_metrics(body: &str) -> String {
+    body.lines()
+        .filter(|line| {
+            let name = match line.strip_prefix('#') {
+                Some(comment) => comment.split_whitespace().nth(1).unwrap_or_default(),
+                None => line.split(['{', ' ']).next().unwrap_or_default(),
+            };
+            !VOLATILE_METRICS.contains(&name)
+        })
+        .map(|line| format!("{line}\n"))
+        .collect()
+}